@@ -0,0 +1,305 @@
+
+use nalgebra::DVector;
+use serde::{Serialize, Deserialize};
+
+/// Input constraints of an Agent, unset limits are not enforced
+/// The speed cap reads the velocity half of the Agent state, the other limits only act on the input
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActuatorLimits {
+
+    /// Largest magnitude of each input component
+    pub max_input: Option<f32>,
+
+    /// Largest norm of the input vector, the direction of the input is kept
+    pub max_input_norm: Option<f32>,
+
+    /// Largest change of each input component per second
+    pub max_rate: Option<f32>,
+
+    /// Largest speed, the input is reduced along the velocity so the speed stays below it
+    pub max_speed: Option<f32>
+
+}
+
+/// Which limits were active on an input
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Saturation {
+    pub input: bool,
+    pub rate: bool,
+    pub speed: bool
+}
+
+impl Saturation {
+
+    pub fn any(&self) -> bool {
+        self.input || self.rate || self.speed
+    }
+
+}
+
+impl ActuatorLimits {
+
+    pub fn is_unbounded(&self) -> bool {
+        self.max_input.is_none() && self.max_input_norm.is_none() && self.max_rate.is_none() && self.max_speed.is_none()
+    }
+
+    /// Input within limits, held over the next step
+    ///  u: commanded input
+    ///  state: [position, velocity] state of the Agent
+    ///  previous: input applied over the previous step, None at the first step
+    ///  dt: duration of the step
+    pub fn apply(&self, u: &DVector<f32>, state: &DVector<f32>, previous: Option<&DVector<f32>>, dt: f32) -> (DVector<f32>, Saturation) {
+
+        let mut u = u.clone();
+        let mut saturation = Saturation::default();
+
+        self.bound(&mut u, previous, dt, &mut saturation);
+
+        // Speed after the step, v + u dt, is kept below the cap by removing the excess acceleration
+        // along the velocity. Applied last so the other limits cannot add it back, the correction is
+        // bounded again so it stays within them
+        if let Some(max_speed) = self.max_speed {
+
+            let n = state.len() / 2;
+            let velocity = state.rows(n, n).clone_owned();
+            let speed = velocity.norm();
+
+            if speed > 0f32 && dt > 0f32 && u.len() == n {
+                let direction = velocity / speed;
+                let along = u.dot(&direction);
+                let allowed = (max_speed - speed) / dt;
+                if along > allowed {
+                    u -= direction * (along - allowed);
+                    saturation.speed = true;
+                    self.bound(&mut u, previous, dt, &mut saturation);
+                }
+            }
+
+        }
+
+        (u, saturation)
+
+    }
+
+    /// Input within limits for continuous feedback, evaluated on the instantaneous state. The rate
+    /// limit is taken from the input at the end of the previous step, and the speed cap becomes a
+    /// boundary condition: at or above the cap the input cannot accelerate along the velocity
+    ///  u: commanded input
+    ///  state: [position, velocity] state of the Agent
+    ///  previous: input at the end of the previous step, None at the first step
+    ///  elapsed: time since the end of the previous step
+    pub fn apply_instantaneous(&self, u: &DVector<f32>, state: &DVector<f32>, previous: Option<&DVector<f32>>, elapsed: f32) -> (DVector<f32>, Saturation) {
+
+        let mut u = u.clone();
+        let mut saturation = Saturation::default();
+
+        self.bound(&mut u, previous, elapsed, &mut saturation);
+
+        if let Some(max_speed) = self.max_speed {
+
+            let n = state.len() / 2;
+            let velocity = state.rows(n, n).clone_owned();
+            let speed = velocity.norm();
+
+            if speed > 0f32 && speed >= max_speed && u.len() == n {
+                let direction = velocity / speed;
+                let along = u.dot(&direction);
+                if along > 0f32 {
+                    u -= direction * along;
+                    saturation.speed = true;
+                    self.bound(&mut u, previous, elapsed, &mut saturation);
+                }
+            }
+
+        }
+
+        (u, saturation)
+
+    }
+
+    /// Applies the rate, per component and norm limits
+    fn bound(&self, u: &mut DVector<f32>, previous: Option<&DVector<f32>>, dt: f32, saturation: &mut Saturation) {
+
+        // Change from the previous input
+        if let (Some(max_rate), Some(previous)) = (self.max_rate, previous) {
+
+            let max_change = max_rate * dt;
+            for (ui, previous_i) in u.iter_mut().zip(previous.iter()) {
+                let change = *ui - previous_i;
+                if change.abs() > max_change {
+                    *ui = previous_i + max_change.copysign(change);
+                    saturation.rate = true;
+                }
+            }
+
+        }
+
+        // Magnitude of each component
+        if let Some(max_input) = self.max_input {
+
+            for ui in u.iter_mut() {
+                if ui.abs() > max_input {
+                    *ui = max_input.copysign(*ui);
+                    saturation.input = true;
+                }
+            }
+
+        }
+
+        // Magnitude of the input vector
+        if let Some(max_input_norm) = self.max_input_norm {
+
+            let norm = u.norm();
+            if norm > max_input_norm {
+                *u *= max_input_norm / norm;
+                saturation.input = true;
+            }
+
+        }
+
+    }
+
+}
+
+/// Input limits of an Agent and statistics of the inputs it applied
+#[derive(Clone, Debug, Default)]
+pub struct ActuatorState {
+
+    pub limits: ActuatorLimits,
+
+    /// Input applied over the last step, the one at its end with continuous feedback
+    pub input: Option<DVector<f32>>,
+
+    /// Integral of the squared input norm
+    pub effort: f32,
+
+    /// Largest input norm applied
+    pub peak_input: f32,
+
+    /// Time spent with an active input, rate or speed limit
+    pub input_saturated_time: f32,
+    pub rate_limited_time: f32,
+    pub speed_limited_time: f32,
+
+    /// Time the statistics were accumulated over
+    pub total_time: f32
+
+}
+
+impl ActuatorState {
+
+    pub fn new(limits: ActuatorLimits) -> Self {
+        Self { limits, ..Self::default() }
+    }
+
+    /// Limits a commanded input held over the next step and accumulates its statistics
+    pub fn apply(&mut self, u: &DVector<f32>, state: &DVector<f32>, dt: f32) -> DVector<f32> {
+
+        let (u, saturation) = self.limits.apply(u, state, self.input.as_ref(), dt);
+        self.record(&u, saturation, dt);
+
+        u
+
+    }
+
+    /// Accumulates the statistics of an input applied over a duration
+    pub fn record(&mut self, u: &DVector<f32>, saturation: Saturation, dt: f32) {
+
+        self.effort += u.norm_squared() * dt;
+        self.peak_input = self.peak_input.max(u.norm());
+        if saturation.input {
+            self.input_saturated_time += dt;
+        }
+        if saturation.rate {
+            self.rate_limited_time += dt;
+        }
+        if saturation.speed {
+            self.speed_limited_time += dt;
+        }
+        self.total_time += dt;
+        self.input = Some(u.clone());
+
+    }
+
+    /// Fraction of the time spent with an active input, rate and speed limit
+    pub fn saturation_fractions(&self) -> (f32, f32, f32) {
+
+        if self.total_time <= 0f32 {
+            return (0f32, 0f32, 0f32);
+        }
+
+        (
+            self.input_saturated_time / self.total_time,
+            self.rate_limited_time / self.total_time,
+            self.speed_limited_time / self.total_time
+        )
+
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_actuator_limits() {
+
+        let state = DVector::from_vec(vec![0.0, 0.0, 0.0, 0.0]);
+        let u = DVector::from_vec(vec![3.0, -4.0]);
+
+        // Per-axis and norm limits
+        let limits = ActuatorLimits { max_input: Some(2.0), ..ActuatorLimits::default() };
+        let (u_limited, saturation) = limits.apply(&u, &state, None, 0.1);
+        assert_eq!(u_limited, DVector::from_vec(vec![2.0, -2.0]));
+        assert!(saturation.input && !saturation.rate);
+
+        let limits = ActuatorLimits { max_input_norm: Some(1.0), ..ActuatorLimits::default() };
+        let (u_limited, _saturation) = limits.apply(&u, &state, None, 0.1);
+        assert!((u_limited - DVector::from_vec(vec![0.6, -0.8])).norm() < 1E-6);
+
+        // Rate limit from the previous input
+        let limits = ActuatorLimits { max_rate: Some(10.0), ..ActuatorLimits::default() };
+        let previous = DVector::from_vec(vec![0.0, 0.0]);
+        let (u_limited, saturation) = limits.apply(&u, &state, Some(&previous), 0.1);
+        assert_eq!(u_limited, DVector::from_vec(vec![1.0, -1.0]));
+        assert!(saturation.rate);
+
+        // Speed cap only removes acceleration along the velocity
+        let moving = DVector::from_vec(vec![0.0, 0.0, 5.0, 0.0]);
+        let limits = ActuatorLimits { max_speed: Some(5.0), ..ActuatorLimits::default() };
+        let (u_limited, saturation) = limits.apply(&u, &moving, None, 0.1);
+        assert_eq!(u_limited, DVector::from_vec(vec![0.0, -4.0]));
+        assert!(saturation.speed);
+
+        // Overspeeding along (1, 1), the input limit cannot add back acceleration along the velocity
+        // and braking is bounded by it
+        let overspeed = DVector::from_vec(vec![0.0, 0.0, 4.0, 4.0]);
+        let limits = ActuatorLimits { max_input: Some(2.0), max_speed: Some(5.0), ..ActuatorLimits::default() };
+        let (u_limited, saturation) = limits.apply(&DVector::from_vec(vec![5.0, -10.0]), &overspeed, None, 0.1);
+        assert_eq!(u_limited, DVector::from_vec(vec![-2.0, -2.0]));
+        assert!(saturation.speed && saturation.input);
+
+        // Continuous feedback, rate limited from the input at the end of the previous step and no
+        // acceleration along the velocity at the cap
+        let limits = ActuatorLimits { max_rate: Some(10.0), max_speed: Some(5.0), ..ActuatorLimits::default() };
+        let (u_limited, saturation) = limits.apply_instantaneous(&u, &moving, None, 0.0);
+        assert_eq!(u_limited, DVector::from_vec(vec![0.0, -4.0]));
+        assert!(saturation.speed && !saturation.rate);
+        let (u_limited, saturation) = limits.apply_instantaneous(&u, &moving, Some(&previous), 0.05);
+        assert_eq!(u_limited, DVector::from_vec(vec![0.0, -0.5]));
+        assert!(saturation.speed && saturation.rate);
+        let slower = DVector::from_vec(vec![0.0, 0.0, 4.9, 0.0]);
+        assert_eq!(limits.apply_instantaneous(&u, &slower, None, 0.0), (u.clone(), Saturation::default()));
+
+        // Statistics
+        let mut actuator = ActuatorState::new(ActuatorLimits { max_input: Some(2.0), ..ActuatorLimits::default() });
+        actuator.apply(&u, &state, 0.5);
+        actuator.apply(&DVector::from_vec(vec![1.0, 0.0]), &state, 0.5);
+        assert_eq!(actuator.saturation_fractions(), (0.5, 0.0, 0.0));
+        assert!((actuator.effort - 4.5).abs() < 1E-6);
+
+    }
+
+}
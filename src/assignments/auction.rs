@@ -0,0 +1,305 @@
+
+use std::error::Error;
+use nalgebra::DMatrix;
+
+use crate::assignments::{AssignmentStrategy, AssignmentProblem, AssignmentOutcome, SolverStats};
+
+/// Outcome of a consensus-based auction
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuctionResult {
+
+    /// Target won by each agent according to its own view, if any
+    pub assignment: Vec<Option<usize>>,
+
+    /// Number of bidding/consensus rounds performed
+    pub rounds: u32,
+
+    /// Whether every agent's view stopped changing before max_rounds
+    pub converged: bool
+
+}
+
+/// Decentralized auction (Bertsekas' auction with consensus on prices, as in CBAA)
+///
+/// Every agent keeps its own view of the target prices and winners. Each round, agents not
+/// winning a target in their view bid on their most valuable visible target, then every agent
+/// adopts the highest bid seen among itself and its neighbors. Agents only exchange information
+/// over the communication graph, so disconnected agents may disagree on the winner of a target.
+///  cost: cost of assigning each agent (row) to each target (column)
+///  adjacency: agents each agent can communicate with, None is a complete graph
+///  visibility: targets each agent knows about, None if every target is known
+///  epsilon: minimum bid increment relative to the largest cost
+///  max_rounds: maximum number of bidding/consensus rounds
+pub fn consensus_auction(
+    cost: &DMatrix<f64>,
+    adjacency: Option<&Vec<Vec<bool>>>,
+    visibility: Option<&Vec<Vec<bool>>>,
+    epsilon: f64,
+    max_rounds: u32) -> AuctionResult
+{
+
+    let (nagents, ntargets) = cost.shape();
+
+    let is_neighbor = |i: usize, k: usize| -> bool {
+        i == k || adjacency.map_or(true, |graph| graph[i][k])
+    };
+    let is_visible = |i: usize, j: usize| -> bool {
+        visibility.map_or(true, |graph| graph[i][j])
+    };
+
+    // Normalize the costs agents can see so that epsilon is independent of the cost scale
+    let max_cost = (0..nagents)
+        .flat_map(|i| (0..ntargets).map(move |j| (i, j)))
+        .filter(|(i, j)| is_visible(*i, *j))
+        .fold(0f64, |acc, (i, j)| acc.max(cost[(i, j)].abs()));
+    let scale = if max_cost > 0f64 { 1f64 / max_cost } else { 1f64 };
+
+    // Value of remaining unassigned - any target is preferred at zero price
+    let outside = -3f64;
+
+    // Local views of the target prices and winners
+    let mut prices = vec![vec![0f64; ntargets]; nagents];
+    let mut winners: Vec<Vec<Option<usize>>> = vec![vec![None; ntargets]; nagents];
+
+    let mut rounds = 0;
+    let mut converged = false;
+    while rounds < max_rounds {
+
+        rounds += 1;
+        let mut changed = false;
+
+        // Bidding
+        for i in 0..nagents {
+
+            if winners[i].contains(&Some(i)) {
+                continue;
+            }
+
+            // Best and second best targets including the outside option
+            let mut best: Option<(usize, f64)> = None;
+            let mut second = outside;
+            for j in (0..ntargets).filter(|j| is_visible(i, *j)) {
+                let value = -scale * cost[(i, j)] - prices[i][j];
+                match best {
+                    Some((_, best_value)) if value <= best_value => second = second.max(value),
+                    Some((_, best_value)) => {
+                        second = second.max(best_value);
+                        best = Some((j, value));
+                    },
+                    None => best = Some((j, value))
+                }
+            }
+
+            if let Some((j, value)) = best {
+                if value > outside {
+                    prices[i][j] += value - second + epsilon;
+                    winners[i][j] = Some(i);
+                    changed = true;
+                }
+            }
+
+        }
+
+        // Consensus with neighbors on the highest bid for each target
+        let mut new_prices = prices.clone();
+        let mut new_winners = winners.clone();
+        for i in 0..nagents {
+            for k in (0..nagents).filter(|k| *k != i && is_neighbor(i, *k)) {
+                for j in 0..ntargets {
+
+                    let outbid = prices[k][j] > new_prices[i][j];
+                    let tie = prices[k][j] == new_prices[i][j]
+                        && winners[k][j].is_some()
+                        && (new_winners[i][j].is_none() || winners[k][j] < new_winners[i][j]);
+
+                    if outbid || tie {
+                        new_prices[i][j] = prices[k][j];
+                        new_winners[i][j] = winners[k][j];
+                    }
+
+                }
+            }
+        }
+
+        if new_prices != prices || new_winners != winners {
+            changed = true;
+        }
+
+        prices = new_prices;
+        winners = new_winners;
+
+        if !changed {
+            converged = true;
+            break;
+        }
+
+    }
+
+    let assignment = (0..nagents)
+        .map(|i| (0..ntargets).find(|j| winners[i][*j] == Some(i)))
+        .collect();
+
+    AuctionResult { assignment, rounds, converged }
+
+}
+
+/// Decentralized assignment of Agents to Targets where each agent bids using only the targets
+/// it can see and the prices it hears from its neighbors
+/// NOTE: agent/target weights are ignored
+#[derive(Clone, Debug)]
+pub struct AuctionAssignment {
+
+    /// Minimum bid increment relative to the largest cost
+    pub epsilon: f64,
+
+    /// Maximum number of bidding/consensus rounds
+    pub max_rounds: u32
+
+}
+
+impl AuctionAssignment {
+
+    pub fn new(epsilon: f64, max_rounds: u32) -> Self {
+        Self { epsilon, max_rounds }
+    }
+
+}
+
+impl Default for AuctionAssignment {
+
+    fn default() -> Self {
+        Self { epsilon: 1E-3, max_rounds: 10000 }
+    }
+
+}
+
+impl AssignmentStrategy for AuctionAssignment {
+
+    /// Fails when the auction did not converge within max_rounds, the partial assignment is
+    /// only available through assign_with_stats
+    fn assign(&self, problem: &AssignmentProblem) -> Result<Vec<Vec<u32>>, Box<dyn Error>> {
+
+        match self.assign_with_stats(problem)? {
+            AssignmentOutcome { stats: Some(stats), .. } if !stats.converged => {
+                Err(format!("auction did not converge after {} rounds", stats.rounds).into())
+            },
+            outcome => Ok(outcome.coupling)
+        }
+
+    }
+
+    fn assign_with_stats(&self, problem: &AssignmentProblem) -> Result<AssignmentOutcome, Box<dyn Error>> {
+
+        let (nagents, ntargets) = problem.cost.shape();
+
+        let result = consensus_auction(&problem.cost,
+                                        problem.agent_adjacency.as_ref(),
+                                        problem.target_visibility.as_ref(),
+                                        self.epsilon,
+                                        self.max_rounds);
+
+        // Convert auction winners to binary coupling matrix
+        let mut binary = vec![vec![0; ntargets]; nagents];
+        for (i, j) in result.assignment.iter().enumerate() {
+            if let Some(j) = j {
+                binary[i][*j] = 1;
+            }
+        }
+
+        let stats = SolverStats { rounds: result.rounds, converged: result.converged };
+
+        Ok(AssignmentOutcome { coupling: binary, stats: Some(stats) })
+
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assignments::linear_sum_assignment;
+
+    #[test]
+    fn test_consensus_auction_complete_graph() {
+
+        let cost = DMatrix::<f64>::from_row_slice(4, 4, &[
+            4.0, 1.0, 3.0, 8.0,
+            2.0, 0.0, 5.0, 7.0,
+            3.0, 2.0, 2.0, 1.0,
+            6.0, 4.0, 1.0, 9.0
+        ]);
+
+        let result = consensus_auction(&cost, None, None, 1E-4, 10000);
+        let truth = linear_sum_assignment(&cost).unwrap();
+
+        assert!(result.converged);
+        assert_eq!(result.assignment, truth);
+
+    }
+
+    #[test]
+    fn test_consensus_auction_line_graph() {
+
+        // agents only talk to their immediate neighbors
+        let cost = DMatrix::<f64>::from_row_slice(3, 3, &[
+            1.0, 2.0, 3.0,
+            1.0, 2.0, 3.0,
+            1.0, 2.0, 3.0
+        ]);
+        let adjacency = vec![
+            vec![true, true, false],
+            vec![true, true, true],
+            vec![false, true, true]
+        ];
+
+        let result = consensus_auction(&cost, Some(&adjacency), None, 1E-3, 10000);
+
+        let mut targets: Vec<usize> = result.assignment.iter().map(|j| j.unwrap()).collect();
+        targets.sort();
+
+        assert!(result.converged);
+        assert_eq!(targets, vec![0, 1, 2]);
+
+    }
+
+    #[test]
+    fn test_consensus_auction_more_agents() {
+
+        let cost = DMatrix::<f64>::from_row_slice(3, 2, &[
+            1.0, 9.0,
+            2.0, 8.0,
+            9.0, 1.0
+        ]);
+
+        let result = consensus_auction(&cost, None, None, 1E-4, 10000);
+        let truth = vec![Some(0), None, Some(1)];
+
+        assert!(result.converged);
+        assert_eq!(result.assignment, truth);
+
+    }
+
+    #[test]
+    fn test_auction_assignment_rounds() {
+
+        let cost = DMatrix::<f64>::from_row_slice(2, 2, &[
+            1.0, 2.0,
+            2.0, 1.0
+        ]);
+        let problem = AssignmentProblem::with_cost(vec![vec![0.0]; 2], vec![vec![0.0]; 2], cost);
+
+        let outcome = AuctionAssignment::default().assign_with_stats(&problem).unwrap();
+        let stats = outcome.stats.unwrap();
+        assert_eq!(outcome.coupling, vec![vec![1, 0], vec![0, 1]]);
+        assert!(stats.converged && stats.rounds > 1);
+
+        // stopping at the round limit is reported and fails the plain assignment
+        let limited = AuctionAssignment::new(1E-3, 1);
+        let outcome = limited.assign_with_stats(&problem).unwrap();
+        assert_eq!(outcome.stats, Some(SolverStats { rounds: 1, converged: false }));
+        assert!(limited.assign(&problem).is_err());
+
+    }
+
+}
+
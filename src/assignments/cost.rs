@@ -0,0 +1,230 @@
+#![allow(non_snake_case)]
+
+use nalgebra::{DMatrix, DVector};
+use rot::utils::metrics::{dist, MetricType};
+use mads::ecs::components::LQRComponent;
+use serde::{Serialize, Deserialize};
+
+use crate::assignments::states_to_matrix;
+
+/// Metric used to compute the cost of assigning an agent to a target
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CostMetric {
+
+    /// ||x_i - x_j||
+    Euclidean,
+
+    /// ||x_i - x_j||^2
+    SqEuclidean,
+
+    /// (x_i - x_j)^T W (x_i - x_j) for a positive semi-definite W
+    Mahalanobis(DMatrix<f64>),
+
+    /// Closed-loop quadratic cost (x_i - x_j)^T P_i (x_i - x_j) where P_i is the Riccati solution
+    /// of agent i's own LQRComponent. Always compares the full state
+    LqrCostToGo
+
+}
+
+impl CostMetric {
+
+    /// LQR cost-to-go (x_i - x_j)^T P (x_i - x_j) using the Riccati solution P of a controller
+    /// NOTE: P is defined over the full state, use together with StateProjection::FullState
+    pub fn lqr_cost_to_go(controller: &LQRComponent) -> Option<Self> {
+
+        match controller.solve() {
+            Ok((_K, P)) => Some(CostMetric::Mahalanobis(P.map(|ele| ele as f64))),
+            Err(_) => None
+        }
+
+    }
+
+    /// Cost of assigning a single agent state to a target state
+    /// NOTE: LqrCostToGo must be resolved per agent with lqr_cost_to_go(), otherwise it falls back
+    /// to the squared Euclidean distance
+    pub fn cost(&self, agent_state: &[f32], target_state: &[f32]) -> f64 {
+
+        let error = DVector::<f64>::from_iterator(agent_state.len(),
+            agent_state.iter().zip(target_state.iter()).map(|(xi, xj)| (*xi - *xj) as f64));
+
+        match self {
+            CostMetric::Euclidean => error.norm(),
+            CostMetric::SqEuclidean | CostMetric::LqrCostToGo => error.norm_squared(),
+            CostMetric::Mahalanobis(W) => (error.transpose() * W * &error)[(0, 0)]
+        }
+
+    }
+
+}
+
+/// Portion of an Agent/Target FullState x that is compared by the assignment cost
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum StateProjection {
+
+    /// Position components only, i.e. the first half of a [position, velocity] state
+    Position,
+
+    /// Every FullState component, e.g. position and velocity
+    FullState,
+
+    /// Weighting/projection matrix W applied to the FullState, i.e. W * x
+    Weighted(DMatrix<f32>)
+
+}
+
+impl StateProjection {
+
+    pub fn project(&self, state: &DVector<f32>) -> Vec<f32> {
+
+        match self {
+            StateProjection::Position => state.rows(0, state.len() / 2).iter().cloned().collect(),
+            StateProjection::FullState => state.iter().cloned().collect(),
+            StateProjection::Weighted(W) => (W * state).iter().cloned().collect()
+        }
+
+    }
+
+}
+
+/// Defines how Agent/Target FullStates are compared when building the assignment cost matrix
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AssignmentCost {
+    pub projection: StateProjection,
+    pub metric: CostMetric
+}
+
+impl AssignmentCost {
+
+    pub fn new(projection: StateProjection, metric: CostMetric) -> Self {
+        Self { projection, metric }
+    }
+
+    /// Project a FullState into the space the metric is evaluated in
+    pub fn project(&self, state: &DVector<f32>) -> Vec<f32> {
+
+        match self.metric {
+            CostMetric::LqrCostToGo => StateProjection::FullState.project(state),
+            _ => self.projection.project(state)
+        }
+
+    }
+
+}
+
+impl Default for AssignmentCost {
+
+    fn default() -> Self {
+        Self { projection: StateProjection::Position, metric: CostMetric::SqEuclidean }
+    }
+
+}
+
+/// Cost of assigning each agent state (row) to each target state (column) under a given metric
+pub fn cost_matrix(agent_states: &Vec<Vec<f32>>, target_states: &Vec<Vec<f32>>, metric: &CostMetric) -> DMatrix<f64> {
+
+    if agent_states.is_empty() || target_states.is_empty() {
+        return DMatrix::<f64>::zeros(agent_states.len(), target_states.len());
+    }
+
+    let xs = states_to_matrix(agent_states);
+    let xt = states_to_matrix(target_states);
+
+    match metric {
+
+        CostMetric::Euclidean => dist(&xs, &xt, MetricType::SqEuclidean).map(|ele| ele.max(0f64).sqrt()),
+
+        CostMetric::SqEuclidean => dist(&xs, &xt, MetricType::SqEuclidean),
+
+        _ => {
+            DMatrix::<f64>::from_fn(xs.nrows(), xt.nrows(), |i, j| {
+                metric.cost(&agent_states[i], &target_states[j])
+            })
+        }
+
+    }
+
+}
+
+/// Cost of assigning each agent state (row) to each target state (column) where every agent is
+/// evaluated under its own metric, e.g. heterogeneous LQR controllers across the fleet
+pub fn agent_cost_matrix(agent_states: &Vec<Vec<f32>>, target_states: &Vec<Vec<f32>>, agent_metrics: &[CostMetric]) -> DMatrix<f64> {
+
+    DMatrix::<f64>::from_fn(agent_states.len(), target_states.len(), |i, j| {
+        agent_metrics[i].cost(&agent_states[i], &target_states[j])
+    })
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agents() -> Vec<Vec<f32>> {
+        vec![vec![0.0, 0.0], vec![1.0, 1.0]]
+    }
+
+    fn targets() -> Vec<Vec<f32>> {
+        vec![vec![3.0, 4.0], vec![1.0, 0.0]]
+    }
+
+    #[test]
+    fn test_cost_matrix() {
+
+        let euclidean = cost_matrix(&agents(), &targets(), &CostMetric::Euclidean);
+        let truth = DMatrix::<f64>::from_row_slice(2, 2, &[5.0, 1.0, 13f64.sqrt(), 1.0]);
+        assert!((euclidean - truth).norm() < 1E-9);
+
+        let sq_euclidean = cost_matrix(&agents(), &targets(), &CostMetric::SqEuclidean);
+        let truth = DMatrix::<f64>::from_row_slice(2, 2, &[25.0, 1.0, 13.0, 1.0]);
+        assert!((sq_euclidean - truth).norm() < 1E-9);
+
+        let W = DMatrix::<f64>::from_diagonal(&DVector::from_vec(vec![1.0, 4.0]));
+        let mahalanobis = cost_matrix(&agents(), &targets(), &CostMetric::Mahalanobis(W));
+        let truth = DMatrix::<f64>::from_row_slice(2, 2, &[73.0, 1.0, 40.0, 4.0]);
+        assert!((mahalanobis - truth).norm() < 1E-9);
+
+        // unresolved LQR cost-to-go falls back to the squared Euclidean distance
+        let lqr = cost_matrix(&agents(), &targets(), &CostMetric::LqrCostToGo);
+        let truth = DMatrix::<f64>::from_row_slice(2, 2, &[25.0, 1.0, 13.0, 1.0]);
+        assert!((lqr - truth).norm() < 1E-9);
+
+    }
+
+    #[test]
+    fn test_state_projection() {
+
+        let state = DVector::<f32>::from_vec(vec![1.0, 2.0, 3.0, 4.0]);
+
+        assert_eq!(StateProjection::Position.project(&state), vec![1.0, 2.0]);
+        assert_eq!(StateProjection::FullState.project(&state), vec![1.0, 2.0, 3.0, 4.0]);
+
+        let W = DMatrix::<f32>::from_row_slice(1, 4, &[1.0, 0.0, 1.0, 0.0]);
+        assert_eq!(StateProjection::Weighted(W).project(&state), vec![4.0]);
+
+        // the LQR cost-to-go always compares full states
+        let cost = AssignmentCost::new(StateProjection::Position, CostMetric::LqrCostToGo);
+        assert_eq!(cost.project(&state), vec![1.0, 2.0, 3.0, 4.0]);
+
+    }
+
+    #[test]
+    fn test_lqr_cost_to_go() {
+
+        // 1D double integrator with Q = I, R = 1 has P = [[sqrt(3), 1], [1, sqrt(3)]]
+        let A = DMatrix::<f32>::from_row_slice(2, 2, &[0.0, 1.0, 0.0, 0.0]);
+        let B = DMatrix::<f32>::from_row_slice(2, 1, &[0.0, 1.0]);
+        let controller = LQRComponent::new(A, B, DMatrix::identity(2, 2), DMatrix::identity(1, 1));
+        let metric = CostMetric::lqr_cost_to_go(&controller).unwrap();
+
+        let sqrt3 = 3f64.sqrt();
+        let agents = vec![vec![1.0, 0.0], vec![1.0, 1.0]];
+        let targets = vec![vec![0.0, 0.0]];
+        let cost = agent_cost_matrix(&agents, &targets, &[metric.clone(), metric]);
+
+        assert!((cost[(0, 0)] - sqrt3).abs() < 1E-3);
+        assert!((cost[(1, 0)] - (2.0 + 2.0 * sqrt3)).abs() < 1E-3);
+
+    }
+
+}
+
@@ -0,0 +1,262 @@
+
+use std::error::Error;
+use nalgebra::DMatrix;
+
+use crate::assignments::{AssignmentStrategy, AssignmentProblem};
+
+/// Solves the rectangular linear sum assignment problem for a given cost matrix (Kuhn-Munkres)
+///
+/// Returns, for each row, the column it is matched to. Every row is matched when
+/// nrows <= ncols, otherwise every column is matched and the remaining rows are None.
+/// Infinite costs forbid a pairing, an error is returned when a row (column) cannot be matched.
+pub fn linear_sum_assignment(cost: &DMatrix<f64>) -> Result<Vec<Option<usize>>, Box<dyn Error>> {
+
+    let (nrows, ncols) = cost.shape();
+
+    if nrows == 0 || ncols == 0 {
+        return Ok(vec![None; nrows]);
+    }
+
+    // Algorithm requires rows <= columns - solve the transposed problem otherwise
+    if nrows > ncols {
+
+        let col_to_row = linear_sum_assignment(&cost.transpose())?;
+        let mut row_to_col = vec![None; nrows];
+        for (j, i) in col_to_row.iter().enumerate() {
+            if let Some(i) = i {
+                row_to_col[*i] = Some(j);
+            }
+        }
+
+        return Ok(row_to_col);
+
+    }
+
+    // Row/column potentials and matching, 1-indexed with 0 as a virtual column
+    let mut u = vec![0f64; nrows + 1];
+    let mut v = vec![0f64; ncols + 1];
+    let mut matched_row = vec![0usize; ncols + 1];
+    let mut way = vec![0usize; ncols + 1];
+
+    for i in 1..=nrows {
+
+        matched_row[0] = i;
+        let mut j0 = 0usize;
+        let mut minv = vec![f64::INFINITY; ncols + 1];
+        let mut used = vec![false; ncols + 1];
+
+        // Grow an alternating tree until a free column is reached
+        loop {
+
+            used[j0] = true;
+            let i0 = matched_row[j0];
+            let mut delta = f64::INFINITY;
+            let mut j1 = 0usize;
+
+            for j in 1..=ncols {
+                if !used[j] {
+                    let reduced = cost[(i0 - 1, j - 1)] - u[i0] - v[j];
+                    if reduced < minv[j] {
+                        minv[j] = reduced;
+                        way[j] = j0;
+                    }
+                    if minv[j] < delta {
+                        delta = minv[j];
+                        j1 = j;
+                    }
+                }
+            }
+
+            // Every remaining column is forbidden for the rows in the tree
+            if j1 == 0 {
+                return Err(format!("infeasible assignment: row {} has no finite cost to a free column", i - 1).into());
+            }
+
+            for j in 0..=ncols {
+                if used[j] {
+                    u[matched_row[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    minv[j] -= delta;
+                }
+            }
+
+            j0 = j1;
+            if matched_row[j0] == 0 {
+                break;
+            }
+
+        }
+
+        // Augment along the alternating path
+        loop {
+            let j1 = way[j0];
+            matched_row[j0] = matched_row[j1];
+            j0 = j1;
+            if j0 == 0 {
+                break;
+            }
+        }
+
+    }
+
+    let mut row_to_col = vec![None; nrows];
+    for j in 1..=ncols {
+        if matched_row[j] != 0 {
+            row_to_col[matched_row[j] - 1] = Some(j - 1);
+        }
+    }
+
+    Ok(row_to_col)
+
+}
+
+/// Exact one-to-one assignment of agents (rows) to targets (columns) minimizing the total cost
+pub fn hungarian_assignment(cost: &DMatrix<f64>) -> Result<Vec<Vec<u32>>, Box<dyn Error>> {
+
+    let (nagents, ntargets) = cost.shape();
+
+    // Convert matching to binary coupling matrix
+    let mut binary = vec![vec![0; ntargets]; nagents];
+    for (i, j) in linear_sum_assignment(cost)?.iter().enumerate() {
+        if let Some(j) = j {
+            binary[i][*j] = 1;
+        }
+    }
+
+    Ok(binary)
+
+}
+
+/// Assignment of Agents to Targets using the Hungarian (Kuhn-Munkres) algorithm
+/// Guarantees a permutation when nagents == ntargets and a maximal matching otherwise
+/// NOTE: matching is one-to-one, agent/target weights are ignored
+#[derive(Default)]
+pub struct HungarianAssignment;
+
+impl AssignmentStrategy for HungarianAssignment {
+
+    fn assign(&self, problem: &AssignmentProblem) -> Result<Vec<Vec<u32>>, Box<dyn Error>> {
+
+        let mut binary = hungarian_assignment(&problem.cost)?;
+        problem.remove_invisible(&mut binary);
+
+        Ok(binary)
+
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear_sum_assignment_square() {
+
+        let cost = DMatrix::<f64>::from_row_slice(3, 3, &[
+            4.0, 1.0, 3.0,
+            2.0, 0.0, 5.0,
+            3.0, 2.0, 2.0
+        ]);
+
+        let result = linear_sum_assignment(&cost).unwrap();
+        let truth = vec![Some(1), Some(0), Some(2)];
+
+        assert_eq!(result, truth);
+
+    }
+
+    #[test]
+    fn test_linear_sum_assignment_rectangular() {
+
+        // more rows than columns
+        let cost = DMatrix::<f64>::from_row_slice(3, 2, &[
+            1.0, 9.0,
+            2.0, 8.0,
+            9.0, 1.0
+        ]);
+
+        let result = linear_sum_assignment(&cost).unwrap();
+        let truth = vec![Some(0), None, Some(1)];
+
+        assert_eq!(result, truth);
+
+        // more columns than rows
+        let result = linear_sum_assignment(&cost.transpose()).unwrap();
+        let truth = vec![Some(0), Some(2)];
+
+        assert_eq!(result, truth);
+
+    }
+
+    #[test]
+    fn test_linear_sum_assignment_ties() {
+
+        // all costs equal - still a permutation
+        let cost = DMatrix::<f64>::from_element(4, 4, 1.0);
+        let result = linear_sum_assignment(&cost).unwrap();
+
+        let mut columns: Vec<usize> = result.iter().map(|j| j.unwrap()).collect();
+        columns.sort();
+
+        assert_eq!(columns, vec![0, 1, 2, 3]);
+
+    }
+
+    #[test]
+    fn test_linear_sum_assignment_infeasible() {
+
+        // row 1 has no finite cost, row 0 only reaches column 0
+        let inf = f64::INFINITY;
+        let cost = DMatrix::<f64>::from_row_slice(2, 2, &[
+            1.0, inf,
+            inf, inf
+        ]);
+
+        assert!(linear_sum_assignment(&cost).is_err());
+
+        // forbidden pairings are avoided when a feasible matching exists
+        let cost = DMatrix::<f64>::from_row_slice(2, 2, &[
+            inf, 5.0,
+            1.0, 2.0
+        ]);
+
+        assert_eq!(linear_sum_assignment(&cost).unwrap(), vec![Some(1), Some(0)]);
+        assert!(linear_sum_assignment(&DMatrix::<f64>::zeros(0, 3)).unwrap().is_empty());
+
+    }
+
+    #[test]
+    fn test_hungarian_target_visibility() {
+
+        use crate::assignments::CostMetric;
+
+        // agent 0 only sees the far target, agent 2 sees nothing
+        let agents = vec![vec![0.0], vec![10.0], vec![20.0]];
+        let targets = vec![vec![1.0], vec![11.0]];
+        let mut problem = AssignmentProblem::new(agents, targets, &CostMetric::SqEuclidean);
+        problem.set_target_visibility(vec![
+            vec![false, true],
+            vec![true, true],
+            vec![false, false]
+        ]);
+
+        let result = HungarianAssignment.assign(&problem).unwrap();
+        assert_eq!(result, vec![vec![0, 1], vec![1, 0], vec![0, 0]]);
+
+        // an agent that sees nothing never takes a target another agent can see
+        let mut problem = AssignmentProblem::new(problem.agent_states, problem.target_states, &CostMetric::SqEuclidean);
+        problem.set_target_visibility(vec![
+            vec![false, false],
+            vec![true, true],
+            vec![true, true]
+        ]);
+
+        let result = HungarianAssignment.assign(&problem).unwrap();
+        assert_eq!(result, vec![vec![0, 0], vec![1, 0], vec![0, 1]]);
+
+    }
+
+}
+
@@ -0,0 +1,175 @@
+
+use std::error::Error;
+use nalgebra::DMatrix;
+
+// Assignment cost metrics
+pub mod cost;
+
+// Optimal transport assignment
+pub mod ot;
+
+// Hungarian (Kuhn-Munkres) assignment
+pub mod hungarian;
+
+// Decentralized auction assignment
+pub mod auction;
+
+// Assignment hysteresis
+pub mod switching;
+
+// Assignment update scheduling
+pub mod schedule;
+
+pub use cost::{cost_matrix, agent_cost_matrix, AssignmentCost, CostMetric, StateProjection};
+pub use ot::{ot_assignment, OTAssignment, OtSolverConfig};
+pub use hungarian::{hungarian_assignment, linear_sum_assignment, HungarianAssignment};
+pub use auction::{consensus_auction, AuctionAssignment, AuctionResult};
+pub use switching::SwitchingPolicy;
+pub use schedule::{AssignmentSchedule, AssignmentScheduler};
+
+/// Agent and Target states to be coupled along with the mass each entity carries
+pub struct AssignmentProblem {
+
+    pub agent_states: Vec<Vec<f32>>,
+    pub target_states: Vec<Vec<f32>>,
+
+    /// Cost of assigning each agent (row) to each target (column)
+    pub cost: DMatrix<f64>,
+
+    /// Mass of each agent, e.g. its capacity
+    pub agent_weights: Vec<f64>,
+
+    /// Mass of each target, e.g. its demand
+    pub target_weights: Vec<f64>,
+
+    /// Agents each agent (row) can communicate with (column). None is a complete graph
+    pub agent_adjacency: Option<Vec<Vec<bool>>>,
+
+    /// Targets (column) each agent (row) knows about. None if every target is known
+    pub target_visibility: Option<Vec<Vec<bool>>>
+
+}
+
+impl AssignmentProblem {
+
+    /// Assignment problem where every agent and target carries unit mass
+    pub fn new(agent_states: Vec<Vec<f32>>, target_states: Vec<Vec<f32>>, metric: &CostMetric) -> Self {
+
+        let cost = cost_matrix(&agent_states, &target_states, metric);
+
+        Self::with_cost(agent_states, target_states, cost)
+
+    }
+
+    /// Assignment problem where each agent's costs are evaluated under its own metric
+    pub fn with_agent_metrics(agent_states: Vec<Vec<f32>>, target_states: Vec<Vec<f32>>, agent_metrics: &[CostMetric]) -> Self {
+
+        let cost = agent_cost_matrix(&agent_states, &target_states, agent_metrics);
+
+        Self::with_cost(agent_states, target_states, cost)
+
+    }
+
+    /// Restricts each agent to the targets it knows about by making the others prohibitively expensive.
+    /// Strategies remove any remaining pair with an unknown target from their coupling
+    pub fn set_target_visibility(&mut self, visibility: Vec<Vec<bool>>) {
+
+        let masked = 1E6 * (1f64 + self.cost.max().abs());
+        for (i, row) in visibility.iter().enumerate() {
+            for (j, visible) in row.iter().enumerate() {
+                if !visible {
+                    self.cost[(i, j)] = masked;
+                }
+            }
+        }
+
+        self.target_visibility = Some(visibility);
+
+    }
+
+    /// Whether an agent knows about a target
+    pub fn is_visible(&self, agent: usize, target: usize) -> bool {
+        self.target_visibility.as_ref().map_or(true, |visibility| visibility[agent][target])
+    }
+
+    /// Removes the pairs of a binary coupling between agents and the targets they cannot see. Agents
+    /// without a visible target available are left unassigned
+    pub fn remove_invisible(&self, binary: &mut [Vec<u32>]) {
+
+        for (i, row) in binary.iter_mut().enumerate() {
+            for (j, ele) in row.iter_mut().enumerate() {
+                if !self.is_visible(i, j) {
+                    *ele = 0;
+                }
+            }
+        }
+
+    }
+
+    fn with_cost(agent_states: Vec<Vec<f32>>, target_states: Vec<Vec<f32>>, cost: DMatrix<f64>) -> Self {
+
+        let agent_weights = vec![1f64; agent_states.len()];
+        let target_weights = vec![1f64; target_states.len()];
+
+        Self {
+            agent_states,
+            target_states,
+            cost,
+            agent_weights,
+            target_weights,
+            agent_adjacency: None,
+            target_visibility: None
+        }
+
+    }
+
+}
+
+/// Computes a binary coupling between a set of Agent states and a set of Target states
+///
+/// The returned matrix has one row per agent and one column per target, where a 1 at (i, j)
+/// assigns agent i to target j.
+pub trait AssignmentStrategy: Send + Sync {
+
+    fn assign(&self, problem: &AssignmentProblem) -> Result<Vec<Vec<u32>>, Box<dyn Error>>;
+
+    /// Binary coupling along with the iterations performed by the solver
+    fn assign_with_stats(&self, problem: &AssignmentProblem) -> Result<AssignmentOutcome, Box<dyn Error>> {
+        Ok(AssignmentOutcome { coupling: self.assign(problem)?, stats: None })
+    }
+
+}
+
+/// Binary coupling computed by an AssignmentStrategy
+pub struct AssignmentOutcome {
+    pub coupling: Vec<Vec<u32>>,
+
+    /// None for solvers that do not report their iterations
+    pub stats: Option<SolverStats>
+}
+
+/// Iterations performed by an iterative assignment solver, e.g. the auction
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SolverStats {
+    pub rounds: u32,
+
+    /// Whether the solver stopped before its iteration limit
+    pub converged: bool
+}
+
+/// Stacks a set of states into a matrix where each row is a state, empty without states
+pub fn states_to_matrix(states: &Vec<Vec<f32>>) -> DMatrix<f64> {
+
+    let nstates = states.len();
+    let dim = match states.first() {
+        Some(state) => state.len(),
+        None => return DMatrix::<f64>::zeros(0, 0)
+    };
+
+    let data: Vec<f64> = states.iter()
+        .flat_map(|state| state.iter().map(|ele| *ele as f64))
+        .collect();
+
+    DMatrix::<f64>::from_row_slice(nstates, dim, data.as_slice())
+
+}
@@ -0,0 +1,312 @@
+
+use std::error::Error;
+use nalgebra::{DVector, DMatrix};
+use rot::ot::emd::emd;
+use rot::regularized::sinkhorn::sinkhorn_knopp;
+use rot::unbalanced::unbalanced_sinkhorn::sinkhorn_knopp_unbalanced;
+use serde::{Serialize, Deserialize};
+
+use crate::assignments::{AssignmentStrategy, AssignmentProblem};
+
+/// Optimal transport solver used to compute the agent/target coupling and its parameters
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum OtSolverConfig {
+
+    /// Exact earth mover's distance
+    Emd { max_iter: Option<i32> },
+
+    /// Entropic regularized Sinkhorn-Knopp
+    ///  reg: entropic regularization
+    Sinkhorn { reg: f64, max_iter: Option<i32>, threshold: Option<f64> },
+
+    /// Unbalanced Sinkhorn-Knopp with KL-relaxed marginals
+    ///  reg: entropic regularization
+    ///  reg_m: marginal relaxation
+    SinkhornUnbalanced { reg: f64, reg_m: f64, max_iter: Option<i32>, threshold: Option<f64> },
+
+    /// Repeatedly couples the lowest cost free agent/target pair
+    /// NOTE: agent/target weights are ignored
+    Greedy
+
+}
+
+impl Default for OtSolverConfig {
+
+    fn default() -> Self {
+        OtSolverConfig::Emd { max_iter: None }
+    }
+
+}
+
+pub fn ot_assignment(problem: &AssignmentProblem, solver: &OtSolverConfig) -> Result<Vec<Vec<u32>>, Box<dyn Error>> {
+
+    let nagents = problem.agent_states.len();
+    let ntargets = problem.target_states.len();
+
+    // Weights of discrete distribution masses representing agents/target states
+    // Balanced solvers require each distribution to sum to one. The unbalanced solver keeps the
+    // relative total mass of the targets with respect to the agents
+    let agent_mass: f64 = problem.agent_weights.iter().sum();
+    let target_mass: f64 = problem.target_weights.iter().sum();
+    let target_scale = match solver {
+        OtSolverConfig::SinkhornUnbalanced { .. } => agent_mass,
+        _ => target_mass
+    };
+
+    let mut a = DVector::<f64>::from_iterator(nagents, problem.agent_weights.iter().map(|w| w / agent_mass));
+    let mut b = DVector::<f64>::from_iterator(ntargets, problem.target_weights.iter().map(|w| w / target_scale));
+
+    // Cost between distributions of agent/target states
+    let mut cost = problem.cost.clone();
+
+    // Normalize each cost row by the maximum cost of the targets the agent can see, masked
+    // targets remain prohibitively expensive
+    for (i, mut row) in cost.row_iter_mut().enumerate() {
+        let max_ele = row.iter().enumerate()
+            .filter(|(j, _ele)| problem.is_visible(i, *j))
+            .fold(0f64, |acc, (_j, ele)| acc.max(*ele));
+        if max_ele > 0f64 {
+            row.scale_mut(1f64 / max_ele);
+        }
+    }
+
+    // Get coupling matrix according to a given cost
+    let gamma: DMatrix<f64> = match solver {
+
+        OtSolverConfig::Emd { max_iter } => {
+            emd(&mut a, &mut b, &mut cost, *max_iter, None)?
+        },
+
+        OtSolverConfig::Sinkhorn { reg, max_iter, threshold } => {
+            sinkhorn_knopp(&mut a, &mut b, &mut cost,
+                            *reg, *max_iter, *threshold)?
+        },
+
+        OtSolverConfig::SinkhornUnbalanced { reg, reg_m, max_iter, threshold } => {
+            sinkhorn_knopp_unbalanced(&mut a, &mut b, &mut cost,
+                                        *reg, *reg_m, *max_iter, *threshold)?
+        },
+
+        OtSolverConfig::Greedy => greedy_coupling(&cost)
+
+    };
+
+    let mut binary = binary_coupling(&gamma, &a, &b);
+    problem.remove_invisible(&mut binary);
+
+    Ok(binary)
+
+}
+
+/// Converts a coupling matrix to a binary coupling matrix
+/// An agent is coupled to its most likely target and to any target it transports at least half
+/// of the smaller of the two masses to, e.g. an agent with a larger capacity covering multiple targets
+///  a: agent masses
+///  b: target masses
+fn binary_coupling(gamma: &DMatrix<f64>, a: &DVector<f64>, b: &DVector<f64>) -> Vec<Vec<u32>> {
+
+    let (nagents, ntargets) = gamma.shape();
+
+    let mut binary = vec![vec![0; ntargets]; nagents];
+    for (i, row) in gamma.row_iter().enumerate() {
+        let threshold = row.max();
+
+        // agent received no mass
+        if threshold <= 0f64 {
+            continue;
+        }
+
+        for (j, ele) in row.iter().enumerate() {
+            if ele >= &threshold || *ele >= 0.5 * a[i].min(b[j]) {
+                binary[i][j] = 1;
+            } else {
+                binary[i][j] = 0;
+            }
+        }
+    }
+
+    binary
+
+}
+
+/// Couples each agent to at most one target by taking the cheapest remaining free pair
+fn greedy_coupling(cost: &DMatrix<f64>) -> DMatrix<f64> {
+
+    let (nagents, ntargets) = cost.shape();
+
+    let mut pairs: Vec<(usize, usize)> = (0..nagents)
+        .flat_map(|i| (0..ntargets).map(move |j| (i, j)))
+        .collect();
+    pairs.sort_by(|p1, p2| cost[*p1].partial_cmp(&cost[*p2]).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut agent_free = vec![true; nagents];
+    let mut target_free = vec![true; ntargets];
+    let mut gamma = DMatrix::<f64>::zeros(nagents, ntargets);
+    for (i, j) in pairs {
+        if agent_free[i] && target_free[j] {
+            gamma[(i, j)] = 1f64;
+            agent_free[i] = false;
+            target_free[j] = false;
+        }
+    }
+
+    gamma
+
+}
+
+/// Assignment of Agents to Targets using optimal transport
+#[derive(Default)]
+pub struct OTAssignment {
+    pub solver: OtSolverConfig
+}
+
+impl OTAssignment {
+
+    pub fn new(solver: OtSolverConfig) -> Self {
+        Self { solver }
+    }
+
+}
+
+impl AssignmentStrategy for OTAssignment {
+
+    fn assign(&self, problem: &AssignmentProblem) -> Result<Vec<Vec<u32>>, Box<dyn Error>> {
+
+        ot_assignment(problem, &self.solver)
+
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assignments::CostMetric;
+
+    fn line_problem() -> AssignmentProblem {
+
+        let agents = vec![vec![0.0], vec![10.0], vec![20.0]];
+        let targets = vec![vec![21.0], vec![1.0], vec![11.0]];
+
+        AssignmentProblem::new(agents, targets, &CostMetric::SqEuclidean)
+
+    }
+
+    #[test]
+    fn test_solver_selection() {
+
+        let problem = line_problem();
+        let truth = vec![vec![0, 1, 0], vec![0, 0, 1], vec![1, 0, 0]];
+
+        let solvers = [
+            OtSolverConfig::default(),
+            OtSolverConfig::Sinkhorn { reg: 0.05, max_iter: Some(1000), threshold: None },
+            OtSolverConfig::Greedy
+        ];
+
+        for solver in solvers.iter() {
+            let result = OTAssignment::new(solver.clone()).assign(&problem).unwrap();
+            assert_eq!(result, truth, "{:?}", solver);
+        }
+
+    }
+
+    #[test]
+    fn test_greedy_coupling_ties() {
+
+        // every pair costs the same - still one target per agent and one agent per target
+        let gamma = greedy_coupling(&DMatrix::<f64>::from_element(3, 3, 1.0));
+
+        for i in 0..3 {
+            assert_eq!(gamma.row(i).sum(), 1.0);
+            assert_eq!(gamma.column(i).sum(), 1.0);
+        }
+
+        // more agents than targets leaves the most expensive agent uncoupled
+        let cost = DMatrix::<f64>::from_row_slice(3, 2, &[
+            1.0, 2.0,
+            9.0, 9.0,
+            2.0, 1.0
+        ]);
+        let gamma = greedy_coupling(&cost);
+
+        assert_eq!(gamma.row(1).sum(), 0.0);
+        assert_eq!(gamma.sum(), 2.0);
+
+    }
+
+    #[test]
+    fn test_binary_coupling() {
+
+        let a = DVector::<f64>::from_vec(vec![0.5, 0.5]);
+        let b = DVector::<f64>::from_vec(vec![0.5, 0.5]);
+
+        // fractional coupling keeps the most likely target only
+        let gamma = DMatrix::<f64>::from_row_slice(2, 2, &[
+            0.3, 0.2,
+            0.2, 0.3
+        ]);
+        assert_eq!(binary_coupling(&gamma, &a, &b), vec![vec![1, 0], vec![0, 1]]);
+
+        // tied targets are both kept, an agent without mass is not coupled
+        let gamma = DMatrix::<f64>::from_row_slice(2, 2, &[
+            0.5, 0.5,
+            0.0, 0.0
+        ]);
+        assert_eq!(binary_coupling(&gamma, &a, &b), vec![vec![1, 1], vec![0, 0]]);
+
+    }
+
+    #[test]
+    fn test_capacity_demand() {
+
+        // an agent with capacity 2 covers the two targets next to it
+        let agents = vec![vec![0.0], vec![10.0]];
+        let targets = vec![vec![1.0], vec![2.0], vec![11.0]];
+        let mut problem = AssignmentProblem::new(agents, targets, &CostMetric::SqEuclidean);
+        problem.agent_weights = vec![2.0, 1.0];
+
+        let result = ot_assignment(&problem, &OtSolverConfig::default()).unwrap();
+        assert_eq!(result, vec![vec![1, 1, 0], vec![0, 0, 1]]);
+
+        // a target with demand 2 is covered by two agents
+        let agents = vec![vec![0.0], vec![1.0], vec![10.0]];
+        let targets = vec![vec![0.5], vec![10.5]];
+        let mut problem = AssignmentProblem::new(agents, targets, &CostMetric::SqEuclidean);
+        problem.target_weights = vec![2.0, 1.0];
+
+        let result = ot_assignment(&problem, &OtSolverConfig::default()).unwrap();
+        assert_eq!(result, vec![vec![1, 0], vec![1, 0], vec![0, 1]]);
+
+    }
+
+    #[test]
+    fn test_target_visibility() {
+
+        // masked targets no longer flatten the costs of the visible ones
+        let mut problem = line_problem();
+        problem.set_target_visibility(vec![
+            vec![false, true, true],
+            vec![true, true, true],
+            vec![true, false, true]
+        ]);
+
+        let truth = vec![vec![0, 1, 0], vec![0, 0, 1], vec![1, 0, 0]];
+        for solver in [OtSolverConfig::default(), OtSolverConfig::Sinkhorn { reg: 0.05, max_iter: Some(1000), threshold: None }].iter() {
+            assert_eq!(ot_assignment(&problem, solver).unwrap(), truth, "{:?}", solver);
+        }
+
+        // an agent that sees nothing is not assigned to an invisible target
+        let agents = vec![vec![0.0], vec![10.0]];
+        let targets = vec![vec![1.0], vec![11.0]];
+        let mut problem = AssignmentProblem::new(agents, targets, &CostMetric::SqEuclidean);
+        problem.set_target_visibility(vec![vec![false, false], vec![true, true]]);
+
+        for solver in [OtSolverConfig::default(), OtSolverConfig::Greedy].iter() {
+            assert_eq!(ot_assignment(&problem, solver).unwrap(), vec![vec![0, 0], vec![0, 1]], "{:?}", solver);
+        }
+
+    }
+
+}
+
@@ -0,0 +1,239 @@
+
+use std::collections::HashMap;
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+/// Controls how often the assignment of Agents to Targets is recomputed
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AssignmentSchedule {
+
+    /// Every simulator iteration
+    EveryStep,
+
+    /// Every N simulator iterations
+    EveryNSteps(u32),
+
+    /// Every T seconds of simulation time
+    Period(f32),
+
+    /// Only at the initial time
+    Once,
+
+    /// Only when an agent's position error to its target or a target's displacement since the
+    /// last assignment exceeds a threshold
+    EventTriggered { tracking_error: Option<f32>, target_motion: Option<f32> }
+
+}
+
+impl Default for AssignmentSchedule {
+
+    fn default() -> Self {
+        AssignmentSchedule::EveryStep
+    }
+
+}
+
+/// Decides when an assignment is due according to an AssignmentSchedule
+#[derive(Clone, Debug, Default)]
+pub struct AssignmentScheduler {
+
+    pub schedule: AssignmentSchedule,
+
+    // Iterations since the last assignment
+    steps: u32,
+
+    // Simulation time of the last assignment
+    last_time: Option<f32>,
+
+    // Target positions at the last assignment
+    target_positions: HashMap<Uuid, Vec<f32>>
+
+}
+
+impl AssignmentScheduler {
+
+    pub fn new(schedule: AssignmentSchedule) -> Self {
+
+        Self {
+            schedule,
+            steps: 0,
+            last_time: None,
+            target_positions: HashMap::new()
+        }
+
+    }
+
+    /// Whether the assignment should be recomputed at the current iteration
+    ///  time: current simulation time
+    ///  max_tracking_error: largest agent-to-target position error
+    ///  target_positions: current Target positions
+    pub fn is_due(&self, time: f32, max_tracking_error: f32, target_positions: &HashMap<Uuid, Vec<f32>>) -> bool {
+
+        let last_time = match self.last_time {
+            Some(t) => t,
+            None => return true // nothing has been assigned yet
+        };
+
+        match &self.schedule {
+
+            AssignmentSchedule::EveryStep => true,
+
+            AssignmentSchedule::EveryNSteps(n) => self.steps >= *n,
+
+            AssignmentSchedule::Period(period) => time - last_time >= *period,
+
+            AssignmentSchedule::Once => false,
+
+            AssignmentSchedule::EventTriggered { tracking_error, target_motion } => {
+
+                let error_event = match tracking_error {
+                    Some(threshold) => max_tracking_error > *threshold,
+                    None => false
+                };
+
+                let motion_event = match target_motion {
+                    Some(threshold) => self.max_target_motion(target_positions) > *threshold,
+                    None => false
+                };
+
+                error_event || motion_event
+
+            }
+
+        }
+
+    }
+
+    /// Whether the schedule depends on agent tracking errors
+    pub fn needs_tracking_error(&self) -> bool {
+        matches!(self.schedule, AssignmentSchedule::EventTriggered { tracking_error: Some(_), .. })
+    }
+
+    /// Whether the schedule depends on target motion
+    pub fn needs_target_motion(&self) -> bool {
+        matches!(self.schedule, AssignmentSchedule::EventTriggered { target_motion: Some(_), .. })
+    }
+
+    /// Advance one iteration without assigning
+    pub fn skip(&mut self) {
+        self.steps += 1;
+    }
+
+    /// Record that an assignment was performed
+    pub fn assigned(&mut self, time: f32, target_positions: HashMap<Uuid, Vec<f32>>) {
+        self.steps = 1;
+        self.last_time = Some(time);
+        self.target_positions = target_positions;
+    }
+
+    /// Forget the last assignment so that the next iteration is due, e.g. after the targets change
+    pub fn reset(&mut self) {
+        self.steps = 0;
+        self.last_time = None;
+        self.target_positions.clear();
+    }
+
+    /// Largest Target displacement since the last assignment
+    fn max_target_motion(&self, target_positions: &HashMap<Uuid, Vec<f32>>) -> f32 {
+
+        target_positions.iter()
+            .filter_map(|(id, position)| {
+                self.target_positions.get(id).map(|previous| {
+                    position.iter().zip(previous.iter())
+                        .map(|(p1, p2)| (p1 - p2).powi(2))
+                        .sum::<f32>()
+                        .sqrt()
+                })
+            })
+            .fold(0f32, f32::max)
+
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_step() {
+
+        let mut scheduler = AssignmentScheduler::new(AssignmentSchedule::EveryStep);
+        let targets = HashMap::new();
+
+        for k in 0..3 {
+            let time = k as f32 * 0.1;
+            assert!(scheduler.is_due(time, 0.0, &targets));
+            scheduler.assigned(time, HashMap::new());
+        }
+
+        // every N steps counts skipped iterations
+        let mut scheduler = AssignmentScheduler::new(AssignmentSchedule::EveryNSteps(3));
+        assert!(scheduler.is_due(0.0, 0.0, &targets));
+        scheduler.assigned(0.0, HashMap::new());
+        assert!(!scheduler.is_due(0.1, 0.0, &targets));
+        scheduler.skip();
+        assert!(!scheduler.is_due(0.2, 0.0, &targets));
+        scheduler.skip();
+        assert!(scheduler.is_due(0.3, 0.0, &targets));
+
+    }
+
+    #[test]
+    fn test_period() {
+
+        let mut scheduler = AssignmentScheduler::new(AssignmentSchedule::Period(1.0));
+        let targets = HashMap::new();
+
+        assert!(scheduler.is_due(0.0, 0.0, &targets));
+        scheduler.assigned(0.0, HashMap::new());
+        assert!(!scheduler.is_due(0.5, 0.0, &targets));
+        scheduler.skip();
+        assert!(scheduler.is_due(1.0, 0.0, &targets));
+        scheduler.assigned(1.0, HashMap::new());
+        assert!(!scheduler.is_due(1.9, 0.0, &targets));
+
+        // once only assigns again after a reset
+        let mut scheduler = AssignmentScheduler::new(AssignmentSchedule::Once);
+        scheduler.assigned(0.0, HashMap::new());
+        assert!(!scheduler.is_due(100.0, 0.0, &targets));
+        scheduler.reset();
+        assert!(scheduler.is_due(100.0, 0.0, &targets));
+
+    }
+
+    #[test]
+    fn test_event_triggered() {
+
+        let target = Uuid::new_v4();
+        let at = |x: f32| -> HashMap<Uuid, Vec<f32>> {
+            [(target, vec![x, 0.0, 0.0])].iter().cloned().collect()
+        };
+
+        let schedule = AssignmentSchedule::EventTriggered { tracking_error: Some(2.0), target_motion: Some(1.0) };
+        let mut scheduler = AssignmentScheduler::new(schedule);
+        assert!(scheduler.needs_tracking_error() && scheduler.needs_target_motion());
+
+        scheduler.assigned(0.0, at(0.0));
+
+        // below both thresholds
+        assert!(!scheduler.is_due(1.0, 1.5, &at(0.5)));
+
+        // tracking error event
+        assert!(scheduler.is_due(1.0, 2.5, &at(0.5)));
+
+        // target motion event, measured from the positions at the last assignment
+        assert!(scheduler.is_due(1.0, 0.0, &at(1.5)));
+        scheduler.assigned(1.0, at(1.5));
+        assert!(!scheduler.is_due(2.0, 0.0, &at(2.0)));
+
+        // disabled triggers never fire
+        let schedule = AssignmentSchedule::EventTriggered { tracking_error: None, target_motion: None };
+        let mut scheduler = AssignmentScheduler::new(schedule);
+        scheduler.assigned(0.0, at(0.0));
+        assert!(!scheduler.needs_tracking_error());
+        assert!(!scheduler.is_due(1.0, 100.0, &at(100.0)));
+
+    }
+
+}
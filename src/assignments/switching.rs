@@ -0,0 +1,137 @@
+
+use nalgebra::DMatrix;
+use serde::{Serialize, Deserialize};
+
+/// Discourages agents from changing targets between consecutive assignments
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SwitchingPolicy {
+
+    /// Cost added to every target other than an agent's current target
+    pub penalty: f64,
+
+    /// Minimum time an agent keeps its current target before it is allowed to switch
+    pub min_dwell_time: f32
+
+}
+
+impl SwitchingPolicy {
+
+    pub fn new(penalty: f64, min_dwell_time: f32) -> Self {
+        Self { penalty, min_dwell_time }
+    }
+
+    /// Adds the switching penalty to an assignment cost matrix
+    ///  current: index of each agent's (row) current target (column), if any
+    ///  dwell: time each agent has kept its current target
+    pub fn apply(&self, cost: &mut DMatrix<f64>, current: &[Option<usize>], dwell: &[f32]) {
+
+        // Cost large enough that no agent inside its dwell time is moved to another target
+        let locked = 1E6 * (1f64 + cost.max().abs() + self.penalty.abs());
+
+        for (i, mut row) in cost.row_iter_mut().enumerate() {
+
+            let j_current = match current[i] {
+                Some(j) => j,
+                None => continue
+            };
+
+            let extra = if dwell[i] < self.min_dwell_time { locked } else { self.penalty };
+
+            for (j, ele) in row.iter_mut().enumerate() {
+                if j != j_current {
+                    *ele += extra;
+                }
+            }
+
+        }
+
+    }
+
+}
+
+impl Default for SwitchingPolicy {
+
+    fn default() -> Self {
+        Self { penalty: 0f64, min_dwell_time: 0f32 }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+    use crate::assignments::hungarian_assignment;
+    use crate::ecs::resources::AssignmentHistory;
+
+    #[test]
+    fn test_switching_penalty() {
+
+        // swapping targets is marginally cheaper
+        let cost = DMatrix::<f64>::from_row_slice(2, 2, &[
+            1.0, 0.9,
+            0.9, 1.0
+        ]);
+        let current = [Some(0), Some(1)];
+        let dwell = [10.0, 10.0];
+
+        assert_eq!(hungarian_assignment(&cost).unwrap(), vec![vec![0, 1], vec![1, 0]]);
+
+        // hysteresis keeps the current targets
+        let mut penalized = cost.clone();
+        SwitchingPolicy::new(0.5, 0.0).apply(&mut penalized, &current, &dwell);
+        assert_eq!(hungarian_assignment(&penalized).unwrap(), vec![vec![1, 0], vec![0, 1]]);
+
+    }
+
+    #[test]
+    fn test_dwell_time_lock() {
+
+        // agent 0 would rather take agent 1's target
+        let cost = DMatrix::<f64>::from_row_slice(2, 2, &[
+            5.0, 1.0,
+            1.0, 5.0
+        ]);
+        let current = [Some(0), None];
+        let policy = SwitchingPolicy::new(0.0, 1.0);
+
+        // locked within its dwell time
+        let mut locked = cost.clone();
+        policy.apply(&mut locked, &current, &[0.5, 0.0]);
+        assert_eq!(hungarian_assignment(&locked).unwrap(), vec![vec![1, 0], vec![0, 1]]);
+
+        // free to switch afterwards
+        let mut unlocked = cost.clone();
+        policy.apply(&mut unlocked, &current, &[1.5, 0.0]);
+        assert_eq!(unlocked, cost);
+        assert_eq!(hungarian_assignment(&unlocked).unwrap(), vec![vec![0, 1], vec![1, 0]]);
+
+    }
+
+    #[test]
+    fn test_assignment_history() {
+
+        let agent = Uuid::new_v4();
+        let (target0, target1) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut history = AssignmentHistory::default();
+
+        // the first assignment and keeping a target are not switches
+        history.record(agent, &[target0], 0.0);
+        history.record(agent, &[target0], 1.0);
+        assert_eq!(history.switches[&agent], 0);
+        assert_eq!(history.dwell_time(&agent, 1.5), 1.5);
+
+        history.record(agent, &[target1], 2.0);
+        assert_eq!(history.switches[&agent], 1);
+        assert_eq!(history.current_target(&agent), Some(&target1));
+        assert_eq!(history.dwell_time(&agent, 2.5), 0.5);
+
+        // an empty assignment is ignored
+        history.record(agent, &[], 3.0);
+        history.record(agent, &[target0], 4.0);
+        assert_eq!(history.switches[&agent], 2);
+        assert_eq!(history.dwell_time(&Uuid::new_v4(), 4.0), 0.0);
+
+    }
+
+}
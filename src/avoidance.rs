@@ -0,0 +1,319 @@
+
+use nalgebra::DVector;
+use serde::{Serialize, Deserialize};
+
+/// Filters the nominal control of an Agent to keep a minimum separation to its neighbors and to
+/// obstacle surfaces
+/// Assumes [position, velocity] double integrator states where the control is an acceleration
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub enum CollisionAvoidance {
+
+    /// Agents follow their nominal control
+    #[default]
+    Disabled,
+
+    /// Adds a repulsive acceleration from neighbors closer than the influence radius
+    ///  min_separation: distance at which the repulsion becomes unbounded
+    ///  influence_radius: distance below which neighbors repel
+    ///  gain: strength of the repulsion
+    PotentialField { min_separation: f32, influence_radius: f32, gain: f32 },
+
+    /// Minimally modifies the nominal control, in the least squares sense, such that the
+    /// separation barrier h = |p_i - p_j|^2 - min_separation^2 with respect to every neighbor
+    /// satisfies h'' + 2 gamma h' + gamma^2 h >= 0 (exponential control barrier function).
+    /// Each agent of a pair takes half of the required correction, and all of it for obstacles.
+    /// Inside an obstacle the distance to its surface counts as negative, h = -|p_i - p_j|^2 - min_separation^2
+    ///  min_separation: minimum distance between agents and to obstacles
+    ///  sensing_radius: distance below which neighbors are considered
+    ///  gamma: rate at which agents are allowed to approach the barrier
+    ControlBarrier { min_separation: f32, sensing_radius: f32, gamma: f32 }
+
+}
+
+// Iterations and tolerance of the barrier function quadratic program
+const QP_MAX_ITER: usize = 100;
+const QP_TOLERANCE: f32 = 1E-6;
+
+// Distance below which two positions are considered coincident
+const EPSILON: f32 = 1E-6;
+
+impl CollisionAvoidance {
+
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self, CollisionAvoidance::Disabled)
+    }
+
+    /// Minimum distance kept to other agents and obstacles
+    pub fn min_separation(&self) -> f32 {
+        match self {
+            CollisionAvoidance::Disabled => 0f32,
+            CollisionAvoidance::PotentialField { min_separation, .. } => *min_separation,
+            CollisionAvoidance::ControlBarrier { min_separation, .. } => *min_separation
+        }
+    }
+
+    /// Distance below which neighbors influence the control
+    pub fn range(&self) -> f32 {
+        match self {
+            CollisionAvoidance::Disabled => 0f32,
+            CollisionAvoidance::PotentialField { influence_radius, .. } => *influence_radius,
+            CollisionAvoidance::ControlBarrier { sensing_radius, .. } => *sensing_radius
+        }
+    }
+
+    /// Default avoidance of obstacle surfaces, a control barrier keeping 1 m of clearance
+    pub fn obstacle_default() -> Self {
+        CollisionAvoidance::ControlBarrier { min_separation: 1f32, sensing_radius: 5f32, gamma: 1f32 }
+    }
+
+    /// Safe control of an Agent keeping clear of its neighbors and of obstacles under this setting
+    ///  u: nominal control
+    ///  state: [position, velocity] state of the Agent
+    ///  neighbors: [position, velocity] states of the other Agents
+    ///  obstacles: [position, velocity] states of the obstacle surface points closest to the Agent, and
+    ///  whether the Agent is inside the obstacle
+    pub fn filter(&self,
+        u: &DVector<f32>,
+        state: &DVector<f32>,
+        neighbors: &[&DVector<f32>],
+        obstacles: &[(DVector<f32>, bool)]
+    ) -> DVector<f32>
+    {
+        safe_control(self, self, u, state, neighbors, obstacles)
+    }
+
+    /// Relative positions and velocities of the neighbors and obstacles within range, with the share
+    /// of the separation this Agent is responsible for and the sign of the distance, negative inside
+    /// an obstacle where p points inward
+    fn relative(&self, state: &DVector<f32>, others: Vec<(&DVector<f32>, f32, f32)>) -> Vec<(DVector<f32>, DVector<f32>, f32, f32)> {
+
+        if !self.is_enabled() {
+            return Vec::new();
+        }
+
+        let n = state.len() / 2;
+        let range = self.range();
+
+        others.into_iter()
+            .map(|(other, share, sign)| {
+                let p = state.rows(0, n) - other.rows(0, n);
+                let v = state.rows(n, n) - other.rows(n, n);
+                (p, v, share, sign)
+            })
+            .filter(|(p, _v, _share, sign)| *sign < 0f32 || p.norm() <= range)
+            .collect()
+
+    }
+
+    /// Repulsive acceleration of the potential field, zero for other settings
+    fn repulsion(&self, relative: &[(DVector<f32>, DVector<f32>, f32, f32)], n: usize) -> DVector<f32> {
+
+        let mut repulsion = DVector::<f32>::zeros(n);
+
+        if let CollisionAvoidance::PotentialField { min_separation, influence_radius, gain } = self {
+
+            for (p, _v, _share, sign) in relative.iter() {
+
+                let distance = p.norm();
+                if distance < EPSILON {
+                    continue;
+                }
+
+                // Clearance to the separation, bounded away from zero
+                let rho = (sign * distance - min_separation).max(EPSILON);
+                let rho_0 = (influence_radius - min_separation).max(EPSILON);
+                if rho >= rho_0 {
+                    continue;
+                }
+
+                let magnitude = gain * (1f32 / rho - 1f32 / rho_0) / rho.powi(2);
+                repulsion += p * (sign * magnitude / distance);
+
+            }
+
+        }
+
+        repulsion
+
+    }
+
+    /// Barrier constraints a.u >= b of the control barrier function, none for other settings
+    fn constraints(&self, relative: &[(DVector<f32>, DVector<f32>, f32, f32)]) -> Vec<(DVector<f32>, f32)> {
+
+        match self {
+
+            CollisionAvoidance::ControlBarrier { min_separation, gamma, .. } => {
+
+                // One constraint per neighbor
+                relative.iter()
+                    .filter(|(p, _v, _share, _sign)| p.norm() >= EPSILON)
+                    .map(|(p, v, share, sign)| {
+                        let h = sign * p.norm_squared() - min_separation.powi(2);
+                        let h_dot = 2f32 * sign * p.dot(v);
+                        let a = p * (2f32 * sign);
+                        let b = share * (-2f32 * sign * v.norm_squared() - 2f32 * gamma * h_dot - gamma.powi(2) * h);
+                        (a, b)
+                    })
+                    .collect()
+
+            },
+
+            _ => Vec::new()
+
+        }
+
+    }
+
+}
+
+/// Safe control of an Agent keeping clear of its neighbors under one setting and of obstacles under
+/// another, the barrier constraints of both are solved together
+///  agent_avoidance: separation kept between agents
+///  obstacle_avoidance: clearance kept to obstacle surfaces
+///  u: nominal control
+///  state: [position, velocity] state of the Agent
+///  neighbors: [position, velocity] states of the other Agents
+///  obstacles: [position, velocity] states of the obstacle surface points closest to the Agent, and
+///  whether the Agent is inside the obstacle
+pub fn safe_control(
+    agent_avoidance: &CollisionAvoidance,
+    obstacle_avoidance: &CollisionAvoidance,
+    u: &DVector<f32>,
+    state: &DVector<f32>,
+    neighbors: &[&DVector<f32>],
+    obstacles: &[(DVector<f32>, bool)]
+) -> DVector<f32>
+{
+
+    let n = state.len() / 2;
+
+    // Each agent of a pair takes half of the separation, all of the clearance to obstacles
+    let neighbors = agent_avoidance.relative(state, neighbors.iter().map(|neighbor| (*neighbor, 0.5, 1f32)).collect());
+    let obstacles = obstacle_avoidance.relative(state, obstacles.iter()
+        .map(|(obstacle, inside)| (obstacle, 1f32, if *inside { -1f32 } else { 1f32 }))
+        .collect());
+
+    let u_nominal = u + agent_avoidance.repulsion(&neighbors, n) + obstacle_avoidance.repulsion(&obstacles, n);
+
+    let mut constraints = agent_avoidance.constraints(&neighbors);
+    constraints.extend(obstacle_avoidance.constraints(&obstacles));
+
+    if constraints.is_empty() {
+        u_nominal
+    } else {
+        solve_qp(&u_nominal, &constraints)
+    }
+
+}
+
+/// Projects the nominal control onto the constraints a.u >= b, i.e. solves
+/// min |u - u_nominal|^2 s.t. a_k.u >= b_k with Hildreth's dual coordinate ascent
+fn solve_qp(u_nominal: &DVector<f32>, constraints: &[(DVector<f32>, f32)]) -> DVector<f32> {
+
+    let mut u = u_nominal.clone();
+    let mut multipliers = vec![0f32; constraints.len()];
+
+    for _ in 0..QP_MAX_ITER {
+
+        let mut change = 0f32;
+        for ((a, b), lambda) in constraints.iter().zip(multipliers.iter_mut()) {
+
+            let norm = a.norm_squared();
+            if norm < EPSILON {
+                continue;
+            }
+
+            let updated = (*lambda + (b - a.dot(&u)) / norm).max(0f32);
+            let delta = updated - *lambda;
+            if delta != 0f32 {
+                u += a * delta;
+                *lambda = updated;
+                change = change.max(delta.abs() * norm.sqrt());
+            }
+
+        }
+
+        if change < QP_TOLERANCE {
+            break;
+        }
+
+    }
+
+    u
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_barrier() {
+
+        // Two agents closing in head on along x
+        let agent = DVector::from_vec(vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+        let neighbor = DVector::from_vec(vec![2.0, 0.0, 0.0, -1.0, 0.0, 0.0]);
+        let u = DVector::from_vec(vec![1.0, 0.0, 0.0]);
+
+        let avoidance = CollisionAvoidance::ControlBarrier { min_separation: 1.0, sensing_radius: 10.0, gamma: 1.0 };
+        let u_safe = avoidance.filter(&u, &agent, &[&neighbor], &[]);
+
+        // The agent brakes, p.u >= b
+        let p = DVector::from_vec(vec![-2.0, 0.0, 0.0]);
+        let v = DVector::from_vec(vec![2.0, 0.0, 0.0]);
+        let h = p.norm_squared() - 1.0;
+        let h_dot = 2.0 * p.dot(&v);
+        let b = 0.5 * (-2.0 * v.norm_squared() - 2.0 * h_dot - h);
+        assert!(u_safe[0] < 0.0);
+        assert!((p * 2.0).dot(&u_safe) >= b - 1E-4);
+
+        // Neighbors out of range and disabled avoidance leave the control untouched
+        let far = DVector::from_vec(vec![20.0, 0.0, 0.0, -1.0, 0.0, 0.0]);
+        assert_eq!(avoidance.filter(&u, &agent, &[&far], &[]), u);
+        assert_eq!(CollisionAvoidance::Disabled.filter(&u, &agent, &[&neighbor], &[]), u);
+
+    }
+
+    #[test]
+    fn test_obstacle_avoidance() {
+
+        // Agent heading into an obstacle surface 1.5 ahead along x, next to a neighbor along y
+        let agent = DVector::from_vec(vec![0.0, 0.0, 1.0, 0.0]);
+        let neighbor = DVector::from_vec(vec![0.0, 1.5, 0.0, 0.0]);
+        let surface = DVector::from_vec(vec![1.5, 0.0, 0.0, 0.0]);
+        let u = DVector::from_vec(vec![1.0, 0.0]);
+        let barrier = CollisionAvoidance::ControlBarrier { min_separation: 1.0, sensing_radius: 10.0, gamma: 1.0 };
+
+        // Obstacles are avoided with agent avoidance disabled
+        let u_safe = safe_control(&CollisionAvoidance::Disabled, &barrier, &u, &agent, &[&neighbor], &[(surface.clone(), false)]);
+        assert!(u_safe[0] < 0.0);
+        assert_eq!(u_safe, barrier.filter(&u, &agent, &[], &[(surface.clone(), false)]));
+
+        // and neighbors are ignored by the obstacle setting
+        let u_free = safe_control(&barrier, &CollisionAvoidance::Disabled, &u, &agent, &[&neighbor], &[(surface, false)]);
+        assert_eq!(u_free, u);
+
+    }
+
+    #[test]
+    fn test_inside_obstacle() {
+
+        // Agent at rest inside an obstacle, 0.5 below the surface point it can leave through along +x
+        let agent = DVector::from_vec(vec![1.5, 0.0, 0.0, 0.0]);
+        let surface = DVector::from_vec(vec![2.0, 0.0, 0.0, 0.0]);
+        let u = DVector::from_vec(vec![-1.0, 0.0]);
+
+        // The barrier pushes the agent out even beyond the sensing radius, 2 |p| u_x >= gamma^2 (|p|^2 + min_separation^2)
+        let avoidance = CollisionAvoidance::ControlBarrier { min_separation: 1.0, sensing_radius: 0.1, gamma: 1.0 };
+        let u_safe = avoidance.filter(&u, &agent, &[], &[(surface.clone(), true)]);
+        assert!((u_safe[0] - 1.25).abs() < 1E-4);
+        assert!(u_safe[1].abs() < 1E-6);
+
+        // as does the repulsive field, while the same point outside would pull it back in
+        let avoidance = CollisionAvoidance::PotentialField { min_separation: 1.0, influence_radius: 3.0, gain: 1.0 };
+        assert!(avoidance.filter(&u, &agent, &[], &[(surface.clone(), true)])[0] > 0.0);
+        assert!(avoidance.filter(&u, &agent, &[], &[(surface, false)])[0] < u[0]);
+
+    }
+
+}
@@ -0,0 +1,459 @@
+
+use std::fs;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+use legion::*;
+use serde::{Serialize, Deserialize};
+
+// MADS
+use mads::simulator::Simulator;
+use mads::simulator::state::SimulatorState;
+use mads::ecs::components::{SimID, FullState};
+
+// formflight
+use crate::config::{ScenarioConfig, ConfigError, AssignmentSolver};
+use crate::assignments::OtSolverConfig;
+use crate::ecs::components::Agent;
+use crate::ecs::resources::{Assignment, AssignmentHistory, SolverConvergence};
+use crate::mission::converged_fraction;
+use crate::safety::SafetyMonitor;
+use crate::post_process::post_process;
+use crate::logger::safety_monitor;
+
+/// Variants of a base scenario to simulate, every combination of the listed values is run.
+/// Empty lists keep the value of the base scenario
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SweepSpec {
+
+    pub base: ScenarioConfig,
+
+    /// Agent/target counts
+    pub counts: Vec<u32>,
+
+    pub solvers: Vec<AssignmentSolver>,
+
+    /// Entropic regularization of the Sinkhorn solvers
+    pub regularization: Vec<f64>,
+
+    /// Standard deviation of the initial agent position noise
+    pub noise: Vec<f32>,
+
+    /// Monte Carlo runs per variant, each with its own noise seed
+    pub repetitions: u32,
+
+    /// Number of simulations run at the same time
+    pub threads: usize,
+
+    /// Position tolerance used for the converged fraction
+    pub tolerance: f32
+
+}
+
+impl Default for SweepSpec {
+
+    fn default() -> Self {
+
+        Self {
+            base: ScenarioConfig::default(),
+            counts: Vec::new(),
+            solvers: Vec::new(),
+            regularization: Vec::new(),
+            noise: Vec::new(),
+            repetitions: 1,
+            threads: 1,
+            tolerance: 1f32
+        }
+
+    }
+
+}
+
+impl SweepSpec {
+
+    /// Loads a sweep from a .toml or .json file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+
+        let extension = path.as_ref().extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase())
+            .unwrap_or_default();
+
+        let contents = fs::read_to_string(&path)?;
+        match extension.as_str() {
+            "toml" => Ok(toml::from_str(&contents)?),
+            "json" => Ok(serde_json::from_str(&contents)?),
+            _ => Err(ConfigError::UnsupportedFormat(extension))
+        }
+
+    }
+
+    /// Expands the sweep into one scenario per run, each writing to its own output directory
+    pub fn expand(&self) -> Vec<RunSpec> {
+
+        let base = &self.base;
+        let counts = if self.counts.is_empty() { vec![base.agents.count] } else { self.counts.clone() };
+        let solvers = if self.solvers.is_empty() { vec![base.assignment.solver.clone()] } else { self.solvers.clone() };
+        let noise = if self.noise.is_empty() { vec![base.agents.initial_noise] } else { self.noise.clone() };
+
+        let mut runs = Vec::new();
+        for count in counts.iter() {
+            for solver in solvers.iter() {
+                for solver in with_regularization(solver, &self.regularization) {
+                    for sigma in noise.iter() {
+                        for repetition in 0..self.repetitions.max(1) {
+
+                            let index = runs.len();
+                            let mut config = base.clone();
+                            config.agents.count = *count;
+                            config.targets.count = *count;
+                            config.assignment.solver = solver.clone();
+                            config.agents.initial_noise = *sigma;
+                            config.agents.seed = base.agents.seed + repetition as u64;
+                            config.output.directory = Path::new(&base.output.directory)
+                                .join(format!("run_{}", index))
+                                .to_string_lossy()
+                                .into_owned();
+
+                            runs.push(RunSpec { index, config });
+
+                        }
+                    }
+                }
+            }
+        }
+
+        runs
+
+    }
+
+}
+
+/// Copies of a solver for each regularization, solvers without regularization are kept as is
+fn with_regularization(solver: &AssignmentSolver, regularization: &[f64]) -> Vec<AssignmentSolver> {
+
+    if regularization.is_empty() {
+        return vec![solver.clone()];
+    }
+
+    match solver {
+
+        AssignmentSolver::OptimalTransport(OtSolverConfig::Sinkhorn { max_iter, threshold, .. }) => {
+            regularization.iter()
+                .map(|reg| AssignmentSolver::OptimalTransport(OtSolverConfig::Sinkhorn {
+                    reg: *reg, max_iter: *max_iter, threshold: *threshold
+                }))
+                .collect()
+        },
+
+        AssignmentSolver::OptimalTransport(OtSolverConfig::SinkhornUnbalanced { reg_m, max_iter, threshold, .. }) => {
+            regularization.iter()
+                .map(|reg| AssignmentSolver::OptimalTransport(OtSolverConfig::SinkhornUnbalanced {
+                    reg: *reg, reg_m: *reg_m, max_iter: *max_iter, threshold: *threshold
+                }))
+                .collect()
+        },
+
+        _ => vec![solver.clone()]
+
+    }
+
+}
+
+/// A single simulation of a sweep
+#[derive(Clone, Debug)]
+pub struct RunSpec {
+    pub index: usize,
+    pub config: ScenarioConfig
+}
+
+/// Metrics of a single simulation
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct RunMetrics {
+
+    /// Mean final position error of the agents to their assigned targets
+    pub mean_error: f32,
+
+    /// Largest final position error of the agents to their assigned targets
+    pub max_error: f32,
+
+    /// Fraction of agents within tolerance of their assigned targets at the final time
+    pub converged_fraction: f32,
+
+    /// Total number of target switches over all agents
+    pub switches: u32,
+
+    /// Smallest distance between two agents over the run
+    pub min_agent_distance: Option<f32>,
+
+    /// Number of agent-agent and agent-obstacle separation violations
+    pub safety_violations: u32,
+
+    /// Mean and largest number of rounds per assignment of iterative solvers, e.g. the auction
+    pub mean_solver_rounds: Option<f32>,
+    pub max_solver_rounds: Option<u32>,
+
+    /// Number of assignments where an iterative solver stopped at its round limit
+    pub unconverged_assignments: u32,
+
+    /// Number of assignments that failed, the agents kept their previous assignment
+    pub failed_assignments: u32,
+
+    /// Wall clock duration of the simulation
+    pub wall_time: f32
+
+}
+
+/// Row of the sweep summary table
+#[derive(Clone, Debug, Serialize)]
+pub struct RunSummary {
+    pub run: usize,
+    pub agents: u32,
+    pub targets: u32,
+    pub solver: String,
+    pub noise: f32,
+    pub seed: u64,
+    pub mean_error: Option<f32>,
+    pub max_error: Option<f32>,
+    pub converged_fraction: Option<f32>,
+    pub switches: Option<u32>,
+    pub min_agent_distance: Option<f32>,
+    pub safety_violations: Option<u32>,
+    pub mean_solver_rounds: Option<f32>,
+    pub max_solver_rounds: Option<u32>,
+    pub unconverged_assignments: Option<u32>,
+    pub failed_assignments: Option<u32>,
+    pub wall_time: Option<f32>,
+    pub error: Option<String>
+}
+
+impl RunSummary {
+
+    fn new(run: &RunSpec, result: Result<RunMetrics, String>) -> Self {
+
+        let config = &run.config;
+        let (metrics, error) = match result {
+            Ok(metrics) => (Some(metrics), None),
+            Err(error) => (None, Some(error))
+        };
+
+        Self {
+            run: run.index,
+            agents: config.agents.count,
+            targets: config.targets.count,
+            solver: format!("{:?}", config.assignment.solver),
+            noise: config.agents.initial_noise,
+            seed: config.agents.seed,
+            mean_error: metrics.as_ref().map(|m| m.mean_error),
+            max_error: metrics.as_ref().map(|m| m.max_error),
+            converged_fraction: metrics.as_ref().map(|m| m.converged_fraction),
+            switches: metrics.as_ref().map(|m| m.switches),
+            min_agent_distance: metrics.as_ref().and_then(|m| m.min_agent_distance),
+            safety_violations: metrics.as_ref().map(|m| m.safety_violations),
+            mean_solver_rounds: metrics.as_ref().and_then(|m| m.mean_solver_rounds),
+            max_solver_rounds: metrics.as_ref().and_then(|m| m.max_solver_rounds),
+            unconverged_assignments: metrics.as_ref().map(|m| m.unconverged_assignments),
+            failed_assignments: metrics.as_ref().map(|m| m.failed_assignments),
+            wall_time: metrics.as_ref().map(|m| m.wall_time),
+            error
+        }
+
+    }
+
+}
+
+/// Simulates a scenario, writes its results and returns its metrics
+///  tolerance: position tolerance used for the converged fraction
+pub fn run_scenario(config: &ScenarioConfig, tolerance: f32) -> Result<RunMetrics, ConfigError> {
+
+    let start = Instant::now();
+
+    // Configure MADS simulator
+    let engine_config = config.engine_config();
+    let sim_config = config.simulator_config();
+    let sim_state = SimulatorState::new(engine_config, sim_config);
+
+    // Configure Scenario
+    let scenario = config.scenario()?;
+
+    // Simulate
+    let mut simulator = Simulator::new(sim_state, scenario);
+    simulator.build();
+    simulator.run();
+
+    // Collision and near-miss metrics recorded during the run, shared by the summary and the exported
+    // results
+    let safety = safety_monitor(simulator.get_state());
+
+    let mut metrics = run_metrics(simulator.get_state(), &safety, tolerance);
+    metrics.wall_time = start.elapsed().as_secs_f32();
+
+    // Post-Process
+    fs::create_dir_all(&config.output.directory)?;
+    post_process(&simulator, &config.output, &safety);
+
+    Ok(metrics)
+
+}
+
+/// Final tracking metrics of a simulation
+fn run_metrics(state: &SimulatorState, safety: &SafetyMonitor, tolerance: f32) -> RunMetrics {
+
+    let assignment = state.ecs.resources.get::<Assignment>().unwrap();
+    let assignment_history = state.ecs.resources.get::<AssignmentHistory>().unwrap();
+    let solver_convergence = state.ecs.resources.get::<SolverConvergence>().unwrap();
+
+    // Position error of each agent to its assigned target, None if unassigned
+    let mut agent_query = <(&SimID, &FullState, &Agent)>::query();
+    let errors: Vec<Option<f32>> = agent_query.iter(&state.ecs.world)
+        .map(|(id, agent_state, _agent)| {
+            match assignment.map.get(&id.uuid) {
+                Some(Some(target_state)) => {
+                    let n = agent_state.data.len() / 2;
+                    Some((agent_state.data.rows(0, n) - target_state.rows(0, n)).norm())
+                },
+                _ => None
+            }
+        })
+        .collect();
+
+    let assigned: Vec<f32> = errors.iter().filter_map(|error| *error).collect();
+    let mean_error = if assigned.is_empty() { 0f32 } else { assigned.iter().sum::<f32>() / assigned.len() as f32 };
+
+    RunMetrics {
+        mean_error,
+        max_error: assigned.iter().cloned().fold(0f32, f32::max),
+        converged_fraction: converged_fraction(&errors, tolerance),
+        switches: assignment_history.switches.values().sum(),
+        min_agent_distance: safety.min_agent_distance,
+        safety_violations: safety.agent_violations + safety.obstacle_violations,
+        mean_solver_rounds: solver_convergence.mean_rounds(),
+        max_solver_rounds: solver_convergence.rounds.iter().max().copied(),
+        unconverged_assignments: solver_convergence.unconverged,
+        failed_assignments: solver_convergence.failed,
+        wall_time: 0f32
+    }
+
+}
+
+/// Runs every simulation of a sweep on a pool of threads and writes a summary table
+/// (summary.csv) to the base output directory
+pub fn run_sweep(spec: &SweepSpec) -> Result<Vec<RunSummary>, ConfigError> {
+
+    let runs = spec.expand();
+    let num_runs = runs.len();
+    let queue = Arc::new(Mutex::new(runs.into_iter()));
+    let (sender, receiver) = mpsc::channel();
+
+    // Each thread takes the next pending run until none are left
+    let handles: Vec<thread::JoinHandle<()>> = (0..spec.threads.max(1).min(num_runs.max(1)))
+        .map(|_| {
+            let queue = Arc::clone(&queue);
+            let sender = sender.clone();
+            let tolerance = spec.tolerance;
+            thread::spawn(move || loop {
+
+                let run = match queue.lock().unwrap().next() {
+                    Some(run) => run,
+                    None => break
+                };
+
+                // A panicking run is reported in its row instead of stopping the thread
+                let result = match panic::catch_unwind(AssertUnwindSafe(|| run_scenario(&run.config, tolerance))) {
+                    Ok(result) => result.map_err(|error| error.to_string()),
+                    Err(payload) => Err(panic_message(payload.as_ref()))
+                };
+                if sender.send(RunSummary::new(&run, result)).is_err() {
+                    break;
+                }
+
+            })
+        })
+        .collect();
+    drop(sender);
+
+    let mut summaries: Vec<RunSummary> = receiver.iter()
+        .enumerate()
+        .map(|(completed, summary)| {
+            println!("run {}/{} done", completed + 1, num_runs);
+            summary
+        })
+        .collect();
+    for handle in handles {
+        if handle.join().is_err() {
+            println!("sweep thread panicked");
+        }
+    }
+    summaries.sort_by_key(|summary| summary.run);
+
+    // Aggregated summary table
+    fs::create_dir_all(&spec.base.output.directory)?;
+    let path = Path::new(&spec.base.output.directory).join("summary.csv");
+    let mut writer = csv::Writer::from_path(path).map_err(std::io::Error::from)?;
+    for summary in summaries.iter() {
+        writer.serialize(summary).map_err(std::io::Error::from)?;
+    }
+    writer.flush()?;
+
+    Ok(summaries)
+
+}
+
+/// Message of a caught panic
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+
+    let message = payload.downcast_ref::<&str>().map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown cause".to_string());
+
+    format!("run panicked, {}", message)
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+
+        let spec = SweepSpec {
+            counts: vec![10, 20],
+            solvers: vec![
+                AssignmentSolver::Hungarian,
+                AssignmentSolver::OptimalTransport(OtSolverConfig::Sinkhorn { reg: 1.0, max_iter: None, threshold: None })
+            ],
+            regularization: vec![0.1, 0.01],
+            noise: vec![0.0, 1.0],
+            repetitions: 2,
+            ..SweepSpec::default()
+        };
+
+        // 2 counts x (1 hungarian + 2 sinkhorn) x 2 noise levels x 2 repetitions
+        let runs = spec.expand();
+        assert_eq!(runs.len(), 24);
+        assert_eq!(runs[23].index, 23);
+        assert_eq!(runs[23].config.agents.count, 20);
+        assert_eq!(runs[23].config.agents.seed, 1);
+        assert_eq!(runs[23].config.assignment.solver,
+            AssignmentSolver::OptimalTransport(OtSolverConfig::Sinkhorn { reg: 0.01, max_iter: None, threshold: None }));
+        assert!(runs[23].config.output.directory.ends_with("run_23"));
+
+    }
+
+    #[test]
+    fn test_panic_message() {
+
+        let payload = panic::catch_unwind(|| panic!("singular matrix")).unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), "run panicked, singular matrix");
+
+        let payload = panic::catch_unwind(|| panic!("{} agents", 3)).unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), "run panicked, 3 agents");
+
+    }
+
+}
@@ -0,0 +1,341 @@
+
+use std::path::PathBuf;
+use clap::{Parser, Subcommand, Args};
+
+// MADS
+use mads::ecs::resources::SimulationTimeHistory;
+
+// formflight
+use crate::config::{ScenarioConfig, ConfigError, AssignmentSolver};
+use crate::distributions::Distribution;
+use crate::assignments::OtSolverConfig;
+use crate::batch::{SweepSpec, run_scenario, run_sweep};
+use crate::logger::{read_results_csv, read_obstacles_json};
+use crate::plot::plot_trajectories;
+
+/// Formation flight simulations with optimal transport based assignment
+#[derive(Parser, Debug)]
+#[clap(name = "formflight", version)]
+pub struct Cli {
+
+    #[clap(subcommand)]
+    pub command: Option<Command>
+
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+
+    /// Simulate a scenario file, or the default scenario when omitted
+    Run {
+        scenario: Option<PathBuf>,
+
+        #[clap(flatten)]
+        overrides: Overrides
+    },
+
+    /// Simulate a scenario for every combination of agent/target counts, solvers, regularizations
+    /// and initial noise levels, and write a summary table of the runs
+    Sweep {
+        /// Sweep specification file, the sweep flags below are added to its variants
+        #[clap(long)]
+        spec: Option<PathBuf>,
+
+        /// Base scenario file, replaces the base scenario of the sweep specification
+        scenario: Option<PathBuf>,
+
+        /// Comma separated agent/target counts, e.g. 10,20,50
+        #[clap(long, value_delimiter = ',')]
+        counts: Vec<u32>,
+
+        /// Comma separated solvers, e.g. emd,hungarian
+        #[clap(long, value_delimiter = ',', value_parser = parse_solver)]
+        solvers: Vec<AssignmentSolver>,
+
+        /// Comma separated Sinkhorn regularizations, e.g. 0.1,0.01
+        #[clap(long, value_delimiter = ',')]
+        regularization: Vec<f64>,
+
+        /// Comma separated standard deviations of the initial agent positions, e.g. 0,0.5,1
+        #[clap(long, value_delimiter = ',')]
+        noise: Vec<f32>,
+
+        /// Monte Carlo runs per variant
+        #[clap(long)]
+        repetitions: Option<u32>,
+
+        /// Number of simulations run in parallel
+        #[clap(long)]
+        threads: Option<usize>,
+
+        #[clap(flatten)]
+        overrides: Overrides
+    },
+
+    /// Plot the trajectories of a previous run
+    Plot {
+        results_dir: PathBuf,
+
+        /// Name of the results file inside the results directory
+        #[clap(long, default_value = "results.csv")]
+        results: String,
+
+        /// Name of the obstacles file inside the results directory, drawn when present
+        #[clap(long, default_value = "obstacles.json")]
+        obstacles: String,
+
+        /// Name of the plot written to the results directory
+        #[clap(long, default_value = "trajectory3d.png")]
+        output: String
+    },
+
+    /// Check that a scenario file can be loaded and built
+    Validate {
+        scenario: PathBuf
+    }
+
+}
+
+/// Settings that take precedence over the scenario file
+#[derive(Args, Debug, Default)]
+pub struct Overrides {
+
+    /// Number of agents
+    #[clap(long)]
+    pub agents: Option<u32>,
+
+    /// Number of targets
+    #[clap(long)]
+    pub targets: Option<u32>,
+
+    /// Agent formation, e.g. sphere:10 or grid2d:2
+    #[clap(long, value_parser = parse_distribution)]
+    pub agent_formation: Option<Distribution>,
+
+    /// Target formation, e.g. circle3d:10 or helix:10,5,3
+    #[clap(long, value_parser = parse_distribution)]
+    pub target_formation: Option<Distribution>,
+
+    /// Assignment solver: emd, sinkhorn:REG, unbalanced:REG,REG_M, greedy, hungarian or auction[:EPSILON]
+    #[clap(long, value_parser = parse_solver)]
+    pub solver: Option<AssignmentSolver>,
+
+    /// Directory the results are written to
+    #[clap(long)]
+    pub output_dir: Option<String>,
+
+    /// Skip plotting the trajectories
+    #[clap(long)]
+    pub no_plot: bool
+
+}
+
+impl Overrides {
+
+    pub fn apply(&self, config: &mut ScenarioConfig) {
+
+        if let Some(agents) = self.agents {
+            config.agents.count = agents;
+        }
+        if let Some(targets) = self.targets {
+            config.targets.count = targets;
+        }
+        if let Some(formation) = &self.agent_formation {
+            config.agents.formation = formation.clone();
+        }
+        if let Some(formation) = &self.target_formation {
+            config.targets.formation = formation.clone();
+        }
+        if let Some(solver) = &self.solver {
+            config.assignment.solver = solver.clone();
+        }
+        if let Some(directory) = &self.output_dir {
+            config.output.directory = directory.clone();
+        }
+        if self.no_plot {
+            config.output.plot = None;
+        }
+
+    }
+
+}
+
+/// Executes a parsed command line
+pub fn execute(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+
+    match cli.command {
+
+        None => { run_scenario(&ScenarioConfig::default(), 1f32)?; },
+
+        Some(Command::Run { scenario, overrides }) => {
+            let mut config = load(scenario)?;
+            overrides.apply(&mut config);
+            run_scenario(&config, 1f32)?;
+        },
+
+        Some(Command::Sweep { spec, scenario, counts, solvers, regularization, noise, repetitions, threads, overrides }) => {
+
+            let mut spec = match spec {
+                Some(path) => SweepSpec::from_file(path)?,
+                None => SweepSpec::default()
+            };
+            if let Some(path) = scenario {
+                spec.base = ScenarioConfig::from_file(path)?;
+            }
+            overrides.apply(&mut spec.base);
+
+            spec.counts.extend(counts);
+            spec.solvers.extend(solvers);
+            spec.regularization.extend(regularization);
+            spec.noise.extend(noise);
+            if let Some(repetitions) = repetitions {
+                spec.repetitions = repetitions;
+            }
+            if let Some(threads) = threads {
+                spec.threads = threads;
+            }
+
+            let summaries = run_sweep(&spec)?;
+            let failed = summaries.iter().filter(|summary| summary.error.is_some()).count();
+            println!("{} runs, {} failed", summaries.len(), failed);
+
+        },
+
+        Some(Command::Plot { results_dir, results, obstacles, output }) => {
+            let data = read_results_csv(&results_dir.join(results).to_string_lossy())?;
+            let times = SimulationTimeHistory { data: Vec::new() };
+            let obstacles_path = results_dir.join(obstacles);
+            let obstacles = if obstacles_path.exists() {
+                read_obstacles_json(&obstacles_path.to_string_lossy())?
+            } else {
+                Vec::new()
+            };
+            plot_trajectories(&times, &data, &obstacles, &results_dir.join(output).to_string_lossy())?;
+        },
+
+        Some(Command::Validate { scenario }) => {
+            let config = ScenarioConfig::from_file(&scenario)?;
+            let scenario = config.scenario()?;
+            println!("{} agents ({:?}), {} targets ({:?}), {} obstacles, {:?}",
+                scenario.num_agents, scenario.agent_formation,
+                scenario.num_targets, scenario.target_formation,
+                scenario.obstacles.len(), scenario.dimension);
+            println!("scenario is valid");
+        }
+
+    }
+
+    Ok(())
+
+}
+
+/// Loads a scenario file, the default scenario when none is given
+fn load(scenario: Option<PathBuf>) -> Result<ScenarioConfig, ConfigError> {
+
+    match scenario {
+        Some(path) => ScenarioConfig::from_file(path),
+        None => Ok(ScenarioConfig::default())
+    }
+
+}
+
+/// Parses a formation given as name:param,param,...
+pub fn parse_distribution(value: &str) -> Result<Distribution, String> {
+
+    let (name, params) = match value.split_once(':') {
+        Some((name, params)) => (name, params),
+        None => (value, "")
+    };
+
+    let params: Vec<f32> = params.split(',')
+        .filter(|param| !param.trim().is_empty())
+        .map(|param| param.trim().parse::<f32>().map_err(|err| format!("{}: {}", param, err)))
+        .collect::<Result<_, _>>()?;
+
+    // Parameter or its default
+    let p = |i: usize, default: f32| *params.get(i).unwrap_or(&default);
+
+    let distribution = match name.to_lowercase().as_str() {
+        "circle2d" => Distribution::Circle2D { radius: p(0, 10f32) },
+        "circle3d" => Distribution::Circle3D { radius: p(0, 10f32) },
+        "sphere" => Distribution::Sphere { radius: p(0, 10f32) },
+        "grid2d" => Distribution::Grid2D { spacing: p(0, 1f32), columns: params.get(1).map(|c| *c as u32) },
+        "grid3d" => Distribution::Grid3D { spacing: p(0, 1f32) },
+        "line" => Distribution::Line { spacing: p(0, 1f32) },
+        "wedge" => Distribution::Wedge { spacing: p(0, 1f32), half_angle: p(1, std::f32::consts::PI / 6f32) },
+        "helix" => Distribution::Helix { radius: p(0, 10f32), pitch: p(1, 5f32), turns: p(2, 3f32) },
+        "torus" => Distribution::Torus { major_radius: p(0, 10f32), minor_radius: p(1, 3f32) },
+        "cube-surface" => Distribution::CubeSurface { half_width: p(0, 10f32) },
+        "cube-volume" => Distribution::CubeVolume { half_width: p(0, 10f32) },
+        "uniform" => Distribution::UniformRandom { radius: p(0, 10f32), seed: p(1, 0f32) as u64 },
+        "gaussian" => Distribution::GaussianRandom { std_dev: p(0, 5f32), seed: p(1, 0f32) as u64 },
+        _ => return Err(format!("unknown formation {}", name))
+    };
+
+    Ok(distribution)
+
+}
+
+/// Parses an assignment solver given as name:param,param,...
+pub fn parse_solver(value: &str) -> Result<AssignmentSolver, String> {
+
+    let (name, params) = match value.split_once(':') {
+        Some((name, params)) => (name, params),
+        None => (value, "")
+    };
+
+    let params: Vec<f64> = params.split(',')
+        .filter(|param| !param.trim().is_empty())
+        .map(|param| param.trim().parse::<f64>().map_err(|err| format!("{}: {}", param, err)))
+        .collect::<Result<_, _>>()?;
+
+    let solver = match (name.to_lowercase().as_str(), params.as_slice()) {
+        ("emd", []) | ("ot", []) => AssignmentSolver::OptimalTransport(OtSolverConfig::Emd { max_iter: None }),
+        ("sinkhorn", [reg]) => {
+            AssignmentSolver::OptimalTransport(OtSolverConfig::Sinkhorn { reg: *reg, max_iter: None, threshold: None })
+        },
+        ("unbalanced", [reg, reg_m]) => {
+            AssignmentSolver::OptimalTransport(OtSolverConfig::SinkhornUnbalanced {
+                reg: *reg, reg_m: *reg_m, max_iter: None, threshold: None
+            })
+        },
+        ("greedy", []) => AssignmentSolver::OptimalTransport(OtSolverConfig::Greedy),
+        ("hungarian", []) => AssignmentSolver::Hungarian,
+        ("auction", []) => AssignmentSolver::Auction { epsilon: 1E-3, max_rounds: 10000 },
+        ("auction", [epsilon]) => AssignmentSolver::Auction { epsilon: *epsilon, max_rounds: 10000 },
+        _ => return Err(format!("unknown solver or wrong number of parameters: {}", value))
+    };
+
+    Ok(solver)
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_arguments() {
+
+        assert_eq!(parse_distribution("sphere").unwrap(), Distribution::Sphere { radius: 10.0 });
+        assert_eq!(parse_distribution("grid2d:2,4").unwrap(), Distribution::Grid2D { spacing: 2.0, columns: Some(4) });
+        assert!(parse_distribution("pyramid").is_err());
+
+        assert_eq!(parse_solver("hungarian").unwrap(), AssignmentSolver::Hungarian);
+        assert_eq!(parse_solver("sinkhorn:0.1").unwrap(),
+            AssignmentSolver::OptimalTransport(OtSolverConfig::Sinkhorn { reg: 0.1, max_iter: None, threshold: None }));
+        assert!(parse_solver("sinkhorn").is_err());
+
+        let cli = Cli::try_parse_from(["formflight", "run", "scenario.toml", "--agents", "10", "--no-plot"]).unwrap();
+        match cli.command {
+            Some(Command::Run { scenario, overrides }) => {
+                assert_eq!(scenario, Some(PathBuf::from("scenario.toml")));
+                assert_eq!(overrides.agents, Some(10));
+                assert!(overrides.no_plot);
+            },
+            _ => panic!("expected run command")
+        }
+
+    }
+
+}
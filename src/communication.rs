@@ -0,0 +1,151 @@
+
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+
+/// Rule used to decide which entities can communicate with or sense each other
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub enum Topology {
+
+    /// Every entity is connected
+    #[default]
+    Complete,
+
+    /// Entities within a given distance are connected
+    Range(f32),
+
+    /// Each entity is connected to its k nearest entities
+    KNearest(usize),
+
+    /// Explicit adjacency list by entity name, e.g. "Agent0" -> ["Agent1", "Target3"]
+    Custom(HashMap<String, Vec<String>>)
+
+}
+
+/// An entity participating in a communication/sensing graph
+pub struct Node<'a> {
+    pub uuid: Uuid,
+    pub name: &'a str,
+    pub position: [f32; 3]
+}
+
+/// Builds the directed edges from each source node to the destination nodes it is connected to
+/// A node is never connected to itself
+pub fn build_edges(sources: &[Node], destinations: &[Node], topology: &Topology) -> HashMap<Uuid, HashSet<Uuid>> {
+
+    let distance = |p1: &[f32; 3], p2: &[f32; 3]| -> f32 {
+        p1.iter().zip(p2.iter())
+            .map(|(x1, x2)| (x1 - x2).powi(2))
+            .sum::<f32>()
+            .sqrt()
+    };
+
+    sources.iter()
+        .map(|source| {
+
+            let candidates = destinations.iter().filter(|destination| destination.uuid != source.uuid);
+
+            let edges: HashSet<Uuid> = match topology {
+
+                Topology::Complete => candidates.map(|destination| destination.uuid).collect(),
+
+                Topology::Range(range) => {
+                    candidates
+                        .filter(|destination| distance(&source.position, &destination.position) <= *range)
+                        .map(|destination| destination.uuid)
+                        .collect()
+                },
+
+                Topology::KNearest(k) => {
+                    let mut sorted: Vec<(f32, Uuid)> = candidates
+                        .map(|destination| (distance(&source.position, &destination.position), destination.uuid))
+                        .collect();
+                    sorted.sort_by(|d1, d2| d1.0.partial_cmp(&d2.0).unwrap_or(std::cmp::Ordering::Equal));
+                    sorted.iter().take(*k).map(|(_d, uuid)| *uuid).collect()
+                },
+
+                Topology::Custom(adjacency) => {
+                    match adjacency.get(source.name) {
+                        Some(names) => {
+                            candidates
+                                .filter(|destination| names.iter().any(|name| name == destination.name))
+                                .map(|destination| destination.uuid)
+                                .collect()
+                        },
+                        None => HashSet::new()
+                    }
+                }
+
+            };
+
+            (source.uuid, edges)
+
+        })
+        .collect()
+
+}
+
+/// Converts graph edges to a boolean adjacency matrix ordered by the given source/destination ids
+pub fn to_adjacency_matrix(edges: &HashMap<Uuid, HashSet<Uuid>>, sources: &[&Uuid], destinations: &[&Uuid]) -> Vec<Vec<bool>> {
+
+    sources.iter()
+        .map(|source| {
+            destinations.iter()
+                .map(|destination| {
+                    match edges.get(*source) {
+                        Some(connected) => connected.contains(*destination),
+                        None => false
+                    }
+                })
+                .collect()
+        })
+        .collect()
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_edges() {
+
+        let ids: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+        let names = ["Agent0", "Agent1", "Agent2", "Agent3"];
+        let positions = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [3.0, 0.0, 0.0], [0.0, 10.0, 0.0]];
+        let nodes: Vec<Node> = (0..4)
+            .map(|i| Node { uuid: ids[i], name: names[i], position: positions[i] })
+            .collect();
+        let connected = |edges: &HashMap<Uuid, HashSet<Uuid>>, i: usize| -> HashSet<Uuid> {
+            edges[&ids[i]].clone()
+        };
+        let set = |indices: &[usize]| -> HashSet<Uuid> { indices.iter().map(|i| ids[*i]).collect() };
+
+        // never connected to itself
+        let edges = build_edges(&nodes, &nodes, &Topology::Complete);
+        assert_eq!(connected(&edges, 0), set(&[1, 2, 3]));
+
+        let edges = build_edges(&nodes, &nodes, &Topology::Range(2.0));
+        assert_eq!(connected(&edges, 0), set(&[1]));
+        assert_eq!(connected(&edges, 1), set(&[0, 2]));
+        assert!(connected(&edges, 3).is_empty());
+
+        let edges = build_edges(&nodes, &nodes, &Topology::KNearest(2));
+        assert_eq!(connected(&edges, 0), set(&[1, 2]));
+        assert_eq!(connected(&edges, 3), set(&[0, 1]));
+
+        let adjacency: HashMap<String, Vec<String>> = [("Agent0".to_string(), vec!["Agent3".to_string(), "Agent0".to_string()])]
+            .iter().cloned().collect();
+        let edges = build_edges(&nodes, &nodes, &Topology::Custom(adjacency));
+        assert_eq!(connected(&edges, 0), set(&[3]));
+        assert!(connected(&edges, 1).is_empty());
+
+        // rows follow the source order, columns the destination order
+        let sources: Vec<&Uuid> = ids.iter().take(2).collect();
+        let destinations: Vec<&Uuid> = ids.iter().collect();
+        let matrix = to_adjacency_matrix(&edges, &sources, &destinations);
+        assert_eq!(matrix, vec![vec![false, false, false, true], vec![false; 4]]);
+
+    }
+
+}
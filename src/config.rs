@@ -0,0 +1,628 @@
+#![allow(non_snake_case)]
+
+use std::fs;
+use std::path::Path;
+use nalgebra::{DMatrix, DVector, Vector3, UnitQuaternion};
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+
+// MADS
+use mads::simulator::configuration::{EngineConfig, SimulatorConfig};
+use mads::math::integrate::IntegratorType;
+
+// formflight
+use crate::tracking_scenario::{TrackingScenario, Dimension};
+use crate::distributions::Distribution;
+use crate::avoidance::CollisionAvoidance;
+use crate::obstacles::{ObstacleShape, ObstacleSpec};
+use crate::motion::TargetMotion;
+use crate::safety::SafetyConfig;
+use crate::actuators::ActuatorLimits;
+use crate::ecs::resources::ControlMode;
+use crate::formation::{FormationPose, FormationAnimation};
+use crate::assignments::{AssignmentStrategy, AssignmentCost, AssignmentSchedule, AssignmentScheduler, SwitchingPolicy};
+use crate::assignments::{OTAssignment, OtSolverConfig, HungarianAssignment, AuctionAssignment};
+
+/// Errors raised while loading a scenario file
+#[derive(Error, Debug)]
+pub enum ConfigError {
+
+    #[error("unable to read scenario file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("unable to parse scenario file: {0}")]
+    Toml(#[from] toml::de::Error),
+
+    #[error("unable to parse scenario file: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("unsupported scenario file format: {0}")]
+    UnsupportedFormat(String),
+
+    #[error("invalid scenario: {0}")]
+    Invalid(String)
+
+}
+
+/// Declarative description of a tracking scenario, its simulation and its outputs
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScenarioConfig {
+    pub agents: AgentsConfig,
+    pub targets: TargetsConfig,
+    pub dynamics: DynamicsConfig,
+    pub assignment: AssignmentConfig,
+
+    /// Static and moving obstacles
+    pub obstacles: Vec<ObstacleConfig>,
+
+    /// Distances below which agents are considered in collision
+    pub safety: SafetyConfig,
+
+    /// Simulation timing, the MADS defaults are used when omitted
+    pub engine: Option<EngineSettings>,
+
+    pub output: OutputConfig
+}
+
+impl ScenarioConfig {
+
+    /// Loads a scenario from a .toml or .json file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+
+        let extension = path.as_ref().extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase())
+            .unwrap_or_default();
+
+        let contents = fs::read_to_string(&path)?;
+        match extension.as_str() {
+            "toml" => Self::from_toml(&contents),
+            "json" => Self::from_json(&contents),
+            _ => Err(ConfigError::UnsupportedFormat(extension))
+        }
+
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    pub fn from_json(contents: &str) -> Result<Self, ConfigError> {
+        Ok(serde_json::from_str(contents)?)
+    }
+
+    /// Builds the TrackingScenario described by the configuration
+    pub fn scenario(&self) -> Result<TrackingScenario, ConfigError> {
+
+        let dimension = self.dynamics.dimension();
+        let mut scenario = TrackingScenario::new(self.agents.count, self.targets.count, self.assignment.solver.strategy());
+
+        scenario.dimension = dimension;
+        scenario.agent_formation = self.agents.formation.clone();
+        scenario.target_formation = self.targets.formation.clone();
+        scenario.agent_pose = self.agents.pose.pose();
+        scenario.initial_noise = self.agents.initial_noise;
+        scenario.seed = self.agents.seed;
+        scenario.collision_avoidance = self.agents.avoidance.clone();
+        scenario.obstacle_avoidance = self.agents.obstacle_avoidance.clone();
+        scenario.actuator_limits = self.agents.actuators.clone();
+        scenario.control_mode = self.agents.control_mode;
+        scenario.target_pose = FormationAnimation::stationary(self.targets.pose.pose());
+
+        // Agent controllers
+        let ControllerConfig::Lqr { q, r } = &self.agents.controller;
+        let n = 2 * dimension.positions();
+        let m = dimension.positions();
+        let Q = match q {
+            Some(q) => q.to_matrix(n, "Q")?,
+            None => DMatrix::<f32>::identity(n, n)
+        };
+        let R = match r {
+            Some(r) => r.to_matrix(m, "R")?,
+            None => DMatrix::<f32>::identity(m, m)
+        };
+        scenario.agent_lqr_weights = vec![(Q, R); self.agents.count as usize];
+
+        // Assignment
+        scenario.assignment_cost = self.assignment.cost.clone();
+        scenario.assignment_schedule = AssignmentScheduler::new(self.assignment.schedule.clone());
+        scenario.switching = self.assignment.switching.clone();
+        scenario.blocked_path_penalty = self.assignment.blocked_path_penalty;
+
+        scenario.safety = self.safety.clone();
+
+        // Obstacles
+        scenario.obstacles = self.obstacles.iter()
+            .map(|obstacle| ObstacleSpec {
+                shape: obstacle.shape.clone(),
+                position: obstacle.position,
+                motion: obstacle.motion.motion()
+            })
+            .collect();
+
+        Ok(scenario)
+
+    }
+
+    /// Builds the MADS engine configuration
+    pub fn engine_config(&self) -> EngineConfig {
+
+        match &self.engine {
+            Some(engine) => EngineConfig::new(
+                engine.start_time,
+                engine.max_time,
+                engine.step,
+                engine.integrator.integrator_type(),
+                engine.integrator_step
+            ),
+            None => EngineConfig::default()
+        }
+
+    }
+
+    /// Builds the MADS simulator configuration
+    pub fn simulator_config(&self) -> SimulatorConfig {
+        SimulatorConfig::default()
+    }
+
+}
+
+impl Default for ScenarioConfig {
+
+    fn default() -> Self {
+
+        Self {
+            agents: AgentsConfig::default(),
+            targets: TargetsConfig::default(),
+            dynamics: DynamicsConfig::default(),
+            assignment: AssignmentConfig::default(),
+            obstacles: Vec::new(),
+            safety: SafetyConfig::default(),
+            engine: None,
+            output: OutputConfig::default()
+        }
+
+    }
+
+}
+
+/// Agent count, initial formation and controller
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AgentsConfig {
+    pub count: u32,
+    pub formation: Distribution,
+    pub pose: PoseConfig,
+    pub controller: ControllerConfig,
+
+    /// Continuous feedback or zero-order hold of the inputs over a simulator step
+    pub control_mode: ControlMode,
+
+    /// Standard deviation of the noise added to the initial positions
+    pub initial_noise: f32,
+
+    /// Seed of the initial position noise
+    pub seed: u64,
+
+    /// Separation kept between agents
+    pub avoidance: CollisionAvoidance,
+
+    /// Clearance kept to obstacles
+    pub obstacle_avoidance: CollisionAvoidance,
+
+    /// Input, rate and speed limits
+    pub actuators: ActuatorLimits
+}
+
+impl Default for AgentsConfig {
+
+    fn default() -> Self {
+
+        Self {
+            count: 50,
+            formation: Distribution::Sphere { radius: 10f32 },
+            pose: PoseConfig::default(),
+            controller: ControllerConfig::default(),
+            control_mode: ControlMode::Continuous,
+            initial_noise: 0f32,
+            seed: 0,
+            avoidance: CollisionAvoidance::Disabled,
+            obstacle_avoidance: CollisionAvoidance::obstacle_default(),
+            actuators: ActuatorLimits::default()
+        }
+
+    }
+
+}
+
+/// Target count and formation
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TargetsConfig {
+    pub count: u32,
+    pub formation: Distribution,
+    pub pose: PoseConfig
+}
+
+impl Default for TargetsConfig {
+
+    fn default() -> Self {
+
+        Self {
+            count: 50,
+            formation: Distribution::Circle3D { radius: 10f32 },
+            pose: PoseConfig { translation: Vector3::new(50f32, 0f32, 0f32), ..PoseConfig::default() }
+        }
+
+    }
+
+}
+
+/// Obstacle shape, initial center position and motion
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ObstacleConfig {
+    pub shape: ObstacleShape,
+    pub position: Vector3<f32>,
+
+    #[serde(default)]
+    pub motion: MotionConfig
+}
+
+/// Motion models selectable from a scenario file
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub enum MotionConfig {
+    #[default]
+    Stationary,
+    ConstantVelocity(Vector3<f32>),
+    Waypoints { waypoints: Vec<Vector3<f32>>, speed: f32, cyclic: bool }
+}
+
+impl MotionConfig {
+
+    pub fn motion(&self) -> TargetMotion {
+
+        match self {
+            MotionConfig::Stationary => TargetMotion::Stationary,
+            MotionConfig::ConstantVelocity(velocity) => TargetMotion::ConstantVelocity(*velocity),
+            MotionConfig::Waypoints { waypoints, speed, cyclic } => {
+                TargetMotion::Waypoints { waypoints: waypoints.clone(), speed: *speed, cyclic: *cyclic }
+            }
+        }
+
+    }
+
+}
+
+/// Placement of a formation, orientation is given as a scaled axis (rad)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PoseConfig {
+    pub translation: Vector3<f32>,
+    pub orientation: Vector3<f32>,
+    pub scale: f32
+}
+
+impl PoseConfig {
+
+    pub fn pose(&self) -> FormationPose {
+        FormationPose::new(self.translation, UnitQuaternion::from_scaled_axis(self.orientation), self.scale)
+    }
+
+}
+
+impl Default for PoseConfig {
+
+    fn default() -> Self {
+        Self { translation: Vector3::zeros(), orientation: Vector3::zeros(), scale: 1f32 }
+    }
+
+}
+
+/// Dynamics model shared by Agents and Targets
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub enum DynamicsConfig {
+
+    DoubleIntegrator2D,
+
+    #[default]
+    DoubleIntegrator3D
+
+}
+
+impl DynamicsConfig {
+
+    pub fn dimension(&self) -> Dimension {
+        match self {
+            DynamicsConfig::DoubleIntegrator2D => Dimension::Planar,
+            DynamicsConfig::DoubleIntegrator3D => Dimension::Spatial
+        }
+    }
+
+}
+
+/// Agent controller and its weights, identity weights are used when omitted
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ControllerConfig {
+    Lqr { q: Option<WeightMatrix>, r: Option<WeightMatrix> }
+}
+
+impl Default for ControllerConfig {
+
+    fn default() -> Self {
+        ControllerConfig::Lqr { q: None, r: None }
+    }
+
+}
+
+/// Square weighting matrix given by its diagonal or by its rows
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum WeightMatrix {
+    Diagonal(Vec<f32>),
+    Rows(Vec<Vec<f32>>)
+}
+
+impl WeightMatrix {
+
+    /// Checks that the matrix is n x n and converts it
+    ///  name: name of the matrix reported on error
+    pub fn to_matrix(&self, n: usize, name: &str) -> Result<DMatrix<f32>, ConfigError> {
+
+        match self {
+
+            WeightMatrix::Diagonal(diagonal) if diagonal.len() == n => {
+                Ok(DMatrix::<f32>::from_diagonal(&DVector::from_vec(diagonal.clone())))
+            },
+
+            WeightMatrix::Rows(rows) if rows.len() == n && rows.iter().all(|row| row.len() == n) => {
+                Ok(DMatrix::<f32>::from_fn(n, n, |i, j| rows[i][j]))
+            },
+
+            _ => Err(ConfigError::Invalid(format!("{} must be {} x {} or a diagonal of length {}", name, n, n, n)))
+
+        }
+
+    }
+
+}
+
+/// Assignment solver, cost and schedule
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AssignmentConfig {
+    pub solver: AssignmentSolver,
+    pub cost: AssignmentCost,
+    pub schedule: AssignmentSchedule,
+    pub switching: Option<SwitchingPolicy>,
+
+    /// Cost added to agent-target pairs whose straight-line path is blocked by an obstacle
+    pub blocked_path_penalty: Option<f64>
+}
+
+/// AssignmentStrategy selectable from a scenario file
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AssignmentSolver {
+    OptimalTransport(OtSolverConfig),
+    Hungarian,
+    Auction { epsilon: f64, max_rounds: u32 }
+}
+
+impl AssignmentSolver {
+
+    pub fn strategy(&self) -> Box<dyn AssignmentStrategy> {
+
+        match self {
+            AssignmentSolver::OptimalTransport(solver) => Box::new(OTAssignment::new(solver.clone())),
+            AssignmentSolver::Hungarian => Box::new(HungarianAssignment),
+            AssignmentSolver::Auction { epsilon, max_rounds } => Box::new(AuctionAssignment::new(*epsilon, *max_rounds))
+        }
+
+    }
+
+}
+
+impl Default for AssignmentSolver {
+
+    fn default() -> Self {
+        AssignmentSolver::OptimalTransport(OtSolverConfig::default())
+    }
+
+}
+
+/// Simulation timing
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EngineSettings {
+    pub start_time: f32,
+    pub max_time: f32,
+
+    /// Time between scenario updates
+    pub step: f32,
+
+    pub integrator: IntegratorConfig,
+    pub integrator_step: f32
+}
+
+/// Numerical integrator selectable from a scenario file
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum IntegratorConfig {
+    RK4,
+    RK45
+}
+
+impl IntegratorConfig {
+
+    pub fn integrator_type(&self) -> IntegratorType {
+        match self {
+            IntegratorConfig::RK4 => IntegratorType::RK4,
+            IntegratorConfig::RK45 => IntegratorType::RK45
+        }
+    }
+
+}
+
+/// Output directory and file names of the post-processed results
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputConfig {
+    pub directory: String,
+    pub results: String,
+    pub assignments: String,
+    pub entities: String,
+    pub mission: String,
+    pub obstacles: String,
+
+    /// Per time step collision metrics and their summary over the run
+    pub safety: String,
+    pub safety_summary: String,
+
+    /// Control effort and time fraction spent at each actuator limit, per agent
+    pub actuators: String,
+
+    /// Trajectory plot, not drawn when omitted
+    pub plot: Option<String>
+}
+
+impl OutputConfig {
+
+    /// Path of an output file inside the output directory
+    pub fn path(&self, filename: &str) -> String {
+        Path::new(&self.directory).join(filename).to_string_lossy().into_owned()
+    }
+
+}
+
+impl Default for OutputConfig {
+
+    fn default() -> Self {
+
+        Self {
+            directory: ".".to_string(),
+            results: "results.csv".to_string(),
+            assignments: "assignments.json".to_string(),
+            entities: "entities.json".to_string(),
+            mission: "mission.json".to_string(),
+            obstacles: "obstacles.json".to_string(),
+            safety: "safety.csv".to_string(),
+            safety_summary: "safety.json".to_string(),
+            actuators: "actuators.csv".to_string(),
+            plot: Some("trajectory3d.png".to_string())
+        }
+
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_toml() {
+
+        let contents = r#"
+            dynamics = "DoubleIntegrator2D"
+
+            [agents]
+            count = 4
+            formation = { Grid2D = { spacing = 2.0 } }
+            controller = { Lqr = { q = [1.0, 1.0, 10.0, 10.0], r = [[2.0, 0.0], [0.0, 2.0]] } }
+            avoidance = { ControlBarrier = { min_separation = 1.0, sensing_radius = 5.0, gamma = 2.0 } }
+            actuators = { max_input_norm = 2.0, max_speed = 5.0 }
+            control_mode = "ZeroOrderHold"
+
+            [targets]
+            count = 4
+            formation = { Circle2D = { radius = 5.0 } }
+            pose = { translation = [20.0, 0.0, 0.0] }
+
+            [assignment]
+            solver = "Hungarian"
+            schedule = { Period = 0.5 }
+            blocked_path_penalty = 100.0
+
+            [[obstacles]]
+            shape = { Cylinder = { radius = 2.0, half_height = 5.0 } }
+            position = [10.0, 0.0, 0.0]
+
+            [[obstacles]]
+            shape = { Box = { half_extents = [1.0, 1.0, 1.0] } }
+            position = [10.0, 5.0, 0.0]
+            motion = { ConstantVelocity = [0.0, -1.0, 0.0] }
+
+            [engine]
+            start_time = 0.0
+            max_time = 10.0
+            step = 0.1
+            integrator = "RK45"
+            integrator_step = 0.1
+
+            [output]
+            directory = "results"
+        "#;
+
+        let config = ScenarioConfig::from_toml(contents).unwrap();
+        assert_eq!(config.agents.formation, Distribution::Grid2D { spacing: 2.0, columns: None });
+        assert_eq!(config.targets.pose.scale, 1.0);
+        assert_eq!(config.assignment.schedule, AssignmentSchedule::Period(0.5));
+        assert_eq!(config.output.results, "results.csv");
+
+        let scenario = config.scenario().unwrap();
+        assert_eq!(scenario.dimension, Dimension::Planar);
+        assert_eq!(scenario.agent_lqr_weights.len(), 4);
+        assert_eq!(scenario.agent_lqr_weights[0].0[(3, 3)], 10.0);
+        assert_eq!(scenario.agent_lqr_weights[0].1[(1, 1)], 2.0);
+        assert_eq!(scenario.collision_avoidance.range(), 5.0);
+        assert_eq!(scenario.actuator_limits.max_speed, Some(5.0));
+        assert_eq!(scenario.actuator_limits.max_rate, None);
+        assert_eq!(scenario.control_mode, ControlMode::ZeroOrderHold);
+        assert_eq!(scenario.obstacles.len(), 2);
+        assert_eq!(scenario.obstacles[0].shape, ObstacleShape::Cylinder { radius: 2.0, half_height: 5.0 });
+        assert_eq!(scenario.blocked_path_penalty, Some(100.0));
+
+    }
+
+    #[test]
+    fn test_invalid_weights() {
+
+        let contents = r#"{ "agents": { "controller": { "Lqr": { "q": [1.0, 1.0], "r": null } } } }"#;
+        let config = ScenarioConfig::from_json(contents).unwrap();
+        assert_eq!(config.agents.count, 50);
+        assert!(matches!(config.scenario(), Err(ConfigError::Invalid(_))));
+
+    }
+
+    #[test]
+    fn test_obstacles_without_avoidance() {
+
+        // Obstacles are avoided without agent-agent avoidance
+        let contents = r#"{ "obstacles": [ { "shape": { "Sphere": { "radius": 1.0 } }, "position": [0.0, 0.0, 0.0] } ] }"#;
+        let config = ScenarioConfig::from_json(contents).unwrap();
+        let scenario = config.scenario().unwrap();
+        assert!(!scenario.collision_avoidance.is_enabled());
+        assert_eq!(scenario.obstacle_avoidance, CollisionAvoidance::obstacle_default());
+
+    }
+
+    #[test]
+    fn test_default_target_pose() {
+
+        use crate::distributions::circle_3d;
+
+        // The configured and programmatic defaults agree
+        let scenario = ScenarioConfig::default().scenario().unwrap();
+        let programmatic = TrackingScenario::new(1, 1, Box::new(HungarianAssignment));
+        assert_eq!(scenario.target_pose, programmatic.target_pose);
+
+        // and reproduce the fixed +50 shift along x of the original target circle
+        for (x, y, z) in circle_3d(10.0, 8) {
+            let slot = Dimension::Spatial.state_at_rest(&Vector3::new(x, y, z));
+            let state = scenario.target_pose.transform_state(0.0, &slot);
+            let baseline = DVector::<f32>::from_vec(vec![x + 50.0, y, z, 0.0, 0.0, 0.0]);
+            assert!((state - baseline).norm() < 1E-4);
+        }
+
+    }
+
+}
+
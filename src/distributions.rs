@@ -1,14 +1,117 @@
 
 use std::f32::consts::PI as pi;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand_distr::StandardNormal;
+use nalgebra::{Vector3, UnitQuaternion};
+use serde::{Serialize, Deserialize};
 
+use crate::point_cloud::PointCloud;
+
+/// Formation shape and its parameters. Positions are relative to the formation origin
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Distribution {
 
-    Circle2D,
-    Circle3D,
-    Sphere
+    Circle2D { radius: f32 },
+    Circle3D { radius: f32 },
+    Sphere { radius: f32 },
+
+    /// Square lattice, columns defaults to the smallest square holding all points
+    Grid2D { spacing: f32, columns: Option<u32> },
+    Grid3D { spacing: f32 },
+    Line { spacing: f32 },
+
+    /// V formation, half_angle between each arm and the -x axis (rad)
+    Wedge { spacing: f32, half_angle: f32 },
+    Helix { radius: f32, pitch: f32, turns: f32 },
+    Torus { major_radius: f32, minor_radius: f32 },
+    CubeSurface { half_width: f32 },
+    CubeVolume { half_width: f32 },
+    UniformRandom { radius: f32, seed: u64 },
+    GaussianRandom { std_dev: f32, seed: u64 },
+
+    /// Points loaded from a point list, mesh or image. Normalized clouds are multiplied by scale
+    PointCloud { cloud: PointCloud, scale: f32 },
+
+    /// Another distribution moved to a center and rotated by an orientation given as a scaled
+    /// axis (rad)
+    Transformed { distribution: Box<Distribution>, center: Vector3<f32>, orientation: Vector3<f32> }
+
+}
+
+impl Distribution {
+
+    /// Computes the x,y,z positions of the distribution for a given number of points
+    pub fn sample(&self, num_samples: u32) -> Vec<Vector3<f32>> {
+
+        let points = match self {
+            Distribution::Circle2D { radius } => circle_3d(*radius, num_samples), // z = 0 plane
+            Distribution::Circle3D { radius } => circle_3d(*radius, num_samples),
+            Distribution::Sphere { radius } => sphere(*radius, num_samples),
+            Distribution::Grid2D { spacing, columns } => match columns {
+                Some(columns) => grid_2d_columns(*spacing, *columns, num_samples),
+                None => grid_2d(*spacing, num_samples)
+            },
+            Distribution::Grid3D { spacing } => grid_3d(*spacing, num_samples),
+            Distribution::Line { spacing } => line(*spacing, num_samples),
+            Distribution::Wedge { spacing, half_angle } => wedge(*spacing, *half_angle, num_samples),
+            Distribution::Helix { radius, pitch, turns } => helix(*radius, *pitch, *turns, num_samples),
+            Distribution::Torus { major_radius, minor_radius } => torus(*major_radius, *minor_radius, num_samples),
+            Distribution::CubeSurface { half_width } => cube_surface(*half_width, num_samples),
+            Distribution::CubeVolume { half_width } => cube_volume(*half_width, num_samples),
+            Distribution::UniformRandom { radius, seed } => uniform_random(*radius, *seed, num_samples),
+            Distribution::GaussianRandom { std_dev, seed } => gaussian_random(*std_dev, *seed, num_samples),
+            Distribution::PointCloud { cloud, scale } => cloud.sample(*scale, num_samples),
+            Distribution::Transformed { distribution, center, orientation } => {
+                let rotation = UnitQuaternion::from_scaled_axis(*orientation);
+                return distribution.sample(num_samples).iter()
+                    .map(|point| center + rotation * point)
+                    .collect();
+            }
+        };
+
+        points.iter().map(|(x, y, z)| Vector3::new(*x, *y, *z)).collect()
+
+    }
+
+}
+
+/// Number of points along each side of the smallest lattice holding num_samples points
+fn lattice_side(num_samples: u32, dimensions: i32) -> u32 {
+
+    let mut side = (num_samples as f32).powf(1f32 / dimensions as f32).round().max(1f32) as u32;
+    while side.pow(dimensions as u32) < num_samples {
+        side += 1;
+    }
+
+    side
+
+}
+
+/// Spacing between points such that a row of side points spans [-radius, radius]
+fn span_spacing(radius: f32, side: u32) -> f32 {
+
+    if side > 1 {
+        2f32 * radius / (side - 1) as f32
+    } else {
+        0f32
+    }
 
 }
 
+/// Selects num_samples points spread evenly through a larger set of points
+fn subsample(points: Vec<(f32, f32, f32)>, num_samples: u32) -> Vec<(f32, f32, f32)> {
+
+    let total = points.len();
+    if total <= num_samples as usize {
+        return points;
+    }
+
+    (0..num_samples as usize)
+        .map(|i| points[i * total / num_samples as usize])
+        .collect()
+
+}
 
 /// Computes the x,y position on a circle for a given number of points
 ///  r: radius of circle
@@ -57,7 +160,7 @@ pub fn sphere(r: f32, num_samples: u32) -> Vec<(f32, f32, f32)> {
             let golden_angle = pi * (3f32 - 5f32.sqrt());
             let theta = golden_angle * i as f32;
             let z_i = (1f32 - 1f32/(num_samples as f32)) * 
-                        (1f32 - (2f32*(i as f32))/(num_samples as f32 - 1f32));
+                        (1f32 - (2f32*(i as f32))/(num_samples as f32 - 1f32).max(1f32));
             let radius = (1f32 - z_i.powf(2f32)).sqrt();
             let x = r * radius * theta.cos();
             let y = r * radius * theta.sin();
@@ -70,9 +173,236 @@ pub fn sphere(r: f32, num_samples: u32) -> Vec<(f32, f32, f32)> {
 
 }
 
+/// Computes the x,y positions on a square lattice in the z = 0 plane, centered on the origin
+///  spacing: distance between neighboring points
+pub fn grid_2d(spacing: f32, num_samples: u32) -> Vec<(f32, f32, f32)> {
+    grid_2d_columns(spacing, lattice_side(num_samples, 2), num_samples)
+}
+
+/// Computes the x,y positions on a lattice with a given number of columns in the z = 0 plane,
+/// centered on the origin
+///  spacing: distance between neighboring points
+///  columns: number of points per row
+pub fn grid_2d_columns(spacing: f32, columns: u32, num_samples: u32) -> Vec<(f32, f32, f32)> {
+
+    let columns = columns.max(1);
+    let rows = ((num_samples + columns - 1) / columns).max(1);
+    let x_offset = spacing * (columns.min(num_samples.max(1)) - 1) as f32 / 2f32;
+    let y_offset = spacing * (rows - 1) as f32 / 2f32;
+
+    let distribution: Vec<(f32, f32, f32)> = (0..num_samples)
+        .map(|i: u32| -> (f32, f32, f32) {
+            let x = spacing * (i % columns) as f32 - x_offset;
+            let y = spacing * (i / columns) as f32 - y_offset;
+            (x, y, 0f32)
+        })
+        .collect();
+
+    distribution
+
+}
+
+/// Computes the x,y,z positions on a cubic lattice, centered on the origin
+///  spacing: distance between neighboring points
+pub fn grid_3d(spacing: f32, num_samples: u32) -> Vec<(f32, f32, f32)> {
+
+    let side = lattice_side(num_samples, 3);
+    let offset = spacing * (side - 1) as f32 / 2f32;
+
+    let distribution: Vec<(f32, f32, f32)> = (0..num_samples)
+        .map(|i: u32| -> (f32, f32, f32) {
+            let x = spacing * (i % side) as f32 - offset;
+            let y = spacing * ((i / side) % side) as f32 - offset;
+            let z = spacing * (i / (side * side)) as f32 - offset;
+            (x, y, z)
+        })
+        .collect();
+
+    distribution
+
+}
+
+/// Computes the positions of evenly spaced points along the x axis, centered on the origin
+///  spacing: distance between neighboring points
+pub fn line(spacing: f32, num_samples: u32) -> Vec<(f32, f32, f32)> {
+
+    let offset = spacing * (num_samples.max(1) - 1) as f32 / 2f32;
+
+    let distribution: Vec<(f32, f32, f32)> = (0..num_samples)
+        .map(|i: u32| -> (f32, f32, f32) {
+            (spacing * i as f32 - offset, 0f32, 0f32)
+        })
+        .collect();
+
+    distribution
+
+}
+
+/// Computes the positions of a V formation in the z = 0 plane with the leader at the origin and
+/// the arms trailing along -x
+///  spacing: distance between neighboring points along an arm
+///  half_angle: angle between each arm and the -x axis (rad)
+pub fn wedge(spacing: f32, half_angle: f32, num_samples: u32) -> Vec<(f32, f32, f32)> {
+
+    let distribution: Vec<(f32, f32, f32)> = (0..num_samples)
+        .map(|i: u32| -> (f32, f32, f32) {
+            // alternate between the left and right arm
+            let rank = ((i + 1) / 2) as f32;
+            let side = if i % 2 == 1 { 1f32 } else { -1f32 };
+            let x = -spacing * rank * half_angle.cos();
+            let y = side * spacing * rank * half_angle.sin();
+            (x, y, 0f32)
+        })
+        .collect();
+
+    distribution
+
+}
+
+/// Computes the positions of points along a helix about the z axis, centered on the origin
+///  radius: radius of the helix
+///  pitch: height gained per turn
+///  turns: number of turns
+pub fn helix(radius: f32, pitch: f32, turns: f32, num_samples: u32) -> Vec<(f32, f32, f32)> {
+
+    let height = pitch * turns;
+    let last = (num_samples.max(2) - 1) as f32;
+
+    let distribution: Vec<(f32, f32, f32)> = (0..num_samples)
+        .map(|i: u32| -> (f32, f32, f32) {
+            let fraction = i as f32 / last;
+            let angle = 2f32 * pi * turns * fraction;
+            let x = radius * angle.cos();
+            let y = radius * angle.sin();
+            let z = height * fraction - height / 2f32;
+            (x, y, z)
+        })
+        .collect();
+
+    distribution
+
+}
+
+/// Computes the positions of points spread over a torus about the z axis
+///  major_radius: distance from the origin to the center of the tube
+///  minor_radius: radius of the tube
+pub fn torus(major_radius: f32, minor_radius: f32, num_samples: u32) -> Vec<(f32, f32, f32)> {
+
+    // The angle around the tube advances by the golden ratio to avoid aligned rings
+    let golden_ratio = (1f32 + 5f32.sqrt()) / 2f32;
+
+    let distribution: Vec<(f32, f32, f32)> = (0..num_samples)
+        .map(|i: u32| -> (f32, f32, f32) {
+            let u = 2f32 * pi * i as f32 / num_samples as f32;
+            let v = 2f32 * pi * (i as f32 * golden_ratio).fract();
+            let ring = major_radius + minor_radius * v.cos();
+            let x = ring * u.cos();
+            let y = ring * u.sin();
+            let z = minor_radius * v.sin();
+            (x, y, z)
+        })
+        .collect();
+
+    distribution
+
+}
+
+/// Computes the positions of points spread over the faces of a cube, centered on the origin
+///  half_width: distance from the center to each face
+pub fn cube_surface(half_width: f32, num_samples: u32) -> Vec<(f32, f32, f32)> {
+
+    // Cube lattice fine enough for its surface to hold the requested number of points. Only the
+    // surface points are kept so that edges and corners shared by the faces appear once
+    let mut side = 2u32;
+    while side.pow(3) - (side - 2).pow(3) < num_samples {
+        side += 1;
+    }
+    let spacing = span_spacing(half_width, side);
+    let coordinate = |i: u32| spacing * i as f32 - half_width;
+
+    let mut points: Vec<(f32, f32, f32)> = Vec::new();
+    for i in 0..side {
+        for j in 0..side {
+            for k in 0..side {
+                let on_surface = [i, j, k].iter().any(|index| *index == 0 || *index == side - 1);
+                if on_surface {
+                    points.push((coordinate(i), coordinate(j), coordinate(k)));
+                }
+            }
+        }
+    }
+
+    subsample(points, num_samples)
+
+}
+
+/// Computes the positions of points filling a cube, centered on the origin
+///  half_width: distance from the center to each face
+pub fn cube_volume(half_width: f32, num_samples: u32) -> Vec<(f32, f32, f32)> {
+
+    let side = lattice_side(num_samples, 3);
+    let spacing = span_spacing(half_width, side);
+    let offset = if side > 1 { half_width } else { 0f32 };
+
+    let points: Vec<(f32, f32, f32)> = (0..side.pow(3))
+        .map(|i: u32| -> (f32, f32, f32) {
+            let x = spacing * (i % side) as f32 - offset;
+            let y = spacing * ((i / side) % side) as f32 - offset;
+            let z = spacing * (i / (side * side)) as f32 - offset;
+            (x, y, z)
+        })
+        .collect();
+
+    subsample(points, num_samples)
+
+}
+
+/// Computes the positions of points drawn uniformly from the inside of a sphere
+///  radius: radius of the sphere
+///  seed: random number generator seed
+pub fn uniform_random(radius: f32, seed: u64, num_samples: u32) -> Vec<(f32, f32, f32)> {
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut distribution: Vec<(f32, f32, f32)> = Vec::with_capacity(num_samples as usize);
+
+    // Rejection sampling from the enclosing cube
+    while distribution.len() < num_samples as usize {
+        let x: f32 = rng.gen_range(-1f32..=1f32);
+        let y: f32 = rng.gen_range(-1f32..=1f32);
+        let z: f32 = rng.gen_range(-1f32..=1f32);
+        if x*x + y*y + z*z <= 1f32 {
+            distribution.push((radius * x, radius * y, radius * z));
+        }
+    }
+
+    distribution
+
+}
+
+/// Computes the positions of points drawn from an isotropic Gaussian centered on the origin
+///  std_dev: standard deviation along each axis
+///  seed: random number generator seed
+pub fn gaussian_random(std_dev: f32, seed: u64, num_samples: u32) -> Vec<(f32, f32, f32)> {
+
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let distribution: Vec<(f32, f32, f32)> = (0..num_samples)
+        .map(|_i: u32| -> (f32, f32, f32) {
+            let x: f32 = rng.sample(StandardNormal);
+            let y: f32 = rng.sample(StandardNormal);
+            let z: f32 = rng.sample(StandardNormal);
+            (std_dev * x, std_dev * y, std_dev * z)
+        })
+        .collect();
+
+    distribution
+
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::point_cloud::Normalization;
 
     #[test]
     fn test_circle_2d() {
@@ -136,4 +466,99 @@ mod tests {
 
     }
 
+    #[test]
+    fn test_grid_3d() {
+
+        let result = grid_3d(1.0, 8);
+        assert_eq!(result.len(), 8);
+        assert!(result.contains(&(-0.5, -0.5, -0.5)));
+        assert!(result.contains(&(0.5, 0.5, 0.5)));
+
+    }
+
+    #[test]
+    fn test_sample_counts() {
+
+        let cloud = PointCloud::new(vec![(0.0, 0.0, 0.0), (1.0, 1.0, 0.0), (2.0, 0.0, 1.0)], Normalization::UnitSphere);
+        let distributions = vec![
+            Distribution::Circle2D { radius: 10.0 },
+            Distribution::Circle3D { radius: 10.0 },
+            Distribution::Sphere { radius: 10.0 },
+            Distribution::Grid2D { spacing: 1.0, columns: None },
+            Distribution::Grid2D { spacing: 1.0, columns: Some(3) },
+            Distribution::Grid3D { spacing: 1.0 },
+            Distribution::Line { spacing: 1.0 },
+            Distribution::Wedge { spacing: 1.0, half_angle: 0.5 },
+            Distribution::Helix { radius: 10.0, pitch: 2.0, turns: 3.0 },
+            Distribution::Torus { major_radius: 10.0, minor_radius: 2.0 },
+            Distribution::CubeSurface { half_width: 10.0 },
+            Distribution::CubeVolume { half_width: 10.0 },
+            Distribution::UniformRandom { radius: 10.0, seed: 0 },
+            Distribution::GaussianRandom { std_dev: 5.0, seed: 0 },
+            Distribution::PointCloud { cloud, scale: 10.0 }
+        ];
+
+        for distribution in distributions.iter() {
+            for num_samples in [1, 7, 50].iter() {
+                let result = distribution.sample(*num_samples);
+                assert_eq!(result.len(), *num_samples as usize, "{:?}", distribution);
+                assert!(result.iter().all(|p| p.iter().all(|c| c.is_finite())));
+            }
+        }
+
+    }
+
+    #[test]
+    fn test_transformed() {
+
+        let distribution = Distribution::Transformed {
+            distribution: Box::new(Distribution::Line { spacing: 2.0 }),
+            center: Vector3::new(0.0, 0.0, 5.0),
+            orientation: Vector3::new(0.0, 0.0, pi / 2.0)
+        };
+
+        let result = distribution.sample(2);
+        assert!((result[0] - Vector3::new(0.0, -1.0, 5.0)).norm() < 1E-6);
+        assert!((result[1] - Vector3::new(0.0, 1.0, 5.0)).norm() < 1E-6);
+
+        let json = serde_json::to_string(&distribution).unwrap();
+        let deserialized: Distribution = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, distribution);
+
+    }
+
+    #[test]
+    fn test_random_seeded() {
+
+        assert_eq!(uniform_random(10.0, 3, 20), uniform_random(10.0, 3, 20));
+        assert!(uniform_random(10.0, 3, 20).iter().all(|(x, y, z)| x*x + y*y + z*z <= 100.0 + 1E-3));
+        assert_eq!(gaussian_random(1.0, 3, 20), gaussian_random(1.0, 3, 20));
+
+    }
+
+    #[test]
+    fn test_cube_surface_distinct() {
+
+        for num_samples in [1, 8, 26, 50, 100].iter() {
+
+            let points = cube_surface(1.0, *num_samples);
+            assert_eq!(points.len(), *num_samples as usize);
+
+            for (i, (x1, y1, z1)) in points.iter().enumerate() {
+
+                // on a face
+                assert!((x1.abs().max(y1.abs()).max(z1.abs()) - 1.0).abs() < 1E-6);
+
+                for (x2, y2, z2) in points.iter().skip(i + 1) {
+                    let distance = ((x1 - x2).powi(2) + (y1 - y2).powi(2) + (z1 - z2).powi(2)).sqrt();
+                    assert!(distance > 1E-3, "{} points, duplicate at {:?}", num_samples, (x1, y1, z1));
+                }
+
+            }
+
+        }
+
+    }
+
 }
+
@@ -1,4 +1,8 @@
 
+use nalgebra::DVector;
+
+use crate::motion::TargetMotion;
+
 // NOTE: having the component is enough to flag as an agent
 /// Flags an entity as an Agent
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -12,3 +16,31 @@ pub struct Target(pub bool);
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Obstacle(pub bool);
 
+/// Motion model driving an Obstacle center and the state it started from
+#[derive(Clone, Debug)]
+pub struct ObstacleTrajectory {
+    pub motion: TargetMotion,
+    pub origin: DVector<f32>
+}
+
+
+/// Mass an Agent contributes to the assignment, e.g. how many targets it can service
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Capacity(pub f32);
+
+/// Mass a Target contributes to the assignment, e.g. how many agents it should attract
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Demand(pub f32);
+
+/// Motion model driving a Target and the state it started from, in the target formation frame
+#[derive(Clone, Debug)]
+pub struct TargetTrajectory {
+    pub motion: TargetMotion,
+    pub origin: DVector<f32>,
+
+    /// Simulation time at which the Target was at its origin
+    pub start_time: f32,
+
+    /// Index of the Target's slot in the formation
+    pub slot: u32
+}
@@ -1,9 +1,13 @@
 
 use nalgebra::DVector;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
-use serde::Serialize;
+use serde::{Serialize, Deserialize};
+
+use crate::assignments::{CostMetric, SolverStats};
+use crate::obstacles::ObstacleShape;
+use crate::avoidance::CollisionAvoidance;
 
 /// Number of Agents
 #[derive(Default)]
@@ -17,10 +21,97 @@ pub struct NumTargets(pub u32);
 #[derive(Default)]
 pub struct NumObstacles(pub u32);
 
+/// Clearance kept by the Agents to obstacle surfaces, separate from the separation kept between
+/// Agents
+pub struct ObstacleAvoidance(pub CollisionAvoidance);
+
 /// Assignment history between agents and targets
 #[derive(Default, Debug, Serialize)]
 pub struct AssignmentHistory {
-    pub map: HashMap<Uuid, Vec<Uuid>>
+    pub map: HashMap<Uuid, Vec<Uuid>>,
+
+    /// Number of times each agent changed its target
+    pub switches: HashMap<Uuid, u32>,
+
+    /// Time at which each agent was given its current target
+    pub assigned_since: HashMap<Uuid, f32>
+}
+
+impl AssignmentHistory {
+
+    /// Appends the targets an agent was assigned to at a given time. The last target is the one the
+    /// agent tracks, a switch is counted when it differs from the previously tracked target
+    pub fn record(&mut self, agent: Uuid, targets: &[Uuid], time: f32) {
+
+        let new_target = match targets.last() {
+            Some(target) => *target,
+            None => return
+        };
+
+        let history = self.map.entry(agent).or_insert(Vec::new());
+        match history.last() {
+            Some(previous) if *previous == new_target => (),
+            Some(_) => {
+                *self.switches.entry(agent).or_insert(0) += 1;
+                self.assigned_since.insert(agent, time);
+            },
+            None => {
+                self.switches.entry(agent).or_insert(0);
+                self.assigned_since.insert(agent, time);
+            }
+        }
+
+        history.extend_from_slice(targets);
+
+    }
+
+    /// The target an agent currently tracks
+    pub fn current_target(&self, agent: &Uuid) -> Option<&Uuid> {
+        self.map.get(agent).and_then(|history| history.last())
+    }
+
+    /// Time an agent has kept its current target
+    pub fn dwell_time(&self, agent: &Uuid, time: f32) -> f32 {
+        match self.assigned_since.get(agent) {
+            Some(since) => time - since,
+            None => 0f32
+        }
+    }
+
+}
+
+/// Rounds performed by an iterative assignment solver at every assignment, empty for solvers that
+/// do not report them, and assignments that failed
+#[derive(Default, Debug, Serialize)]
+pub struct SolverConvergence {
+    pub rounds: Vec<u32>,
+
+    /// Number of assignments where the solver stopped at its round limit
+    pub unconverged: u32,
+
+    /// Number of assignments rejected for a solver error or an unconverged result, the previous
+    /// assignment was kept
+    pub failed: u32
+}
+
+impl SolverConvergence {
+
+    pub fn record(&mut self, stats: &SolverStats) {
+        self.rounds.push(stats.rounds);
+        if !stats.converged {
+            self.unconverged += 1;
+        }
+    }
+
+    /// Mean number of rounds per assignment
+    pub fn mean_rounds(&self) -> Option<f32> {
+        if self.rounds.is_empty() {
+            None
+        } else {
+            Some(self.rounds.iter().sum::<u32>() as f32 / self.rounds.len() as f32)
+        }
+    }
+
 }
 
 /// Current assignment as a mapping from Agent uuid to Target state
@@ -28,3 +119,43 @@ pub struct AssignmentHistory {
 pub struct Assignment {
     pub map: HashMap<Uuid, Option<DVector<f32>>>
 }
+
+/// Assignment cost metric of each Agent resolved once at setup, e.g. the Riccati solution of its
+/// LQR controller
+#[derive(Default, Debug)]
+pub struct AgentCostMetrics {
+    pub map: HashMap<Uuid, CostMetric>
+}
+
+/// How the Agent controllers are evaluated while integrating a simulator step
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub enum ControlMode {
+
+    /// Feedback on the instantaneous state x(t), i.e. continuous closed loop
+    #[default]
+    Continuous,
+
+    /// Input computed from the state at the start of the step and held over the step, i.e.
+    /// sampled-data control at the engine step
+    ZeroOrderHold
+
+}
+
+/// Agent states at the start of the current iteration, read by the Agents to avoid each other
+#[derive(Default, Debug)]
+pub struct AgentStates {
+    pub map: HashMap<Uuid, DVector<f32>>
+}
+
+/// Obstacle shapes and [position, velocity] states at the start of the current iteration
+#[derive(Default, Debug)]
+pub struct ObstacleStates {
+    pub map: HashMap<Uuid, (ObstacleShape, DVector<f32>)>
+}
+
+/// Agent-to-Agent communication and Agent-to-Target sensing, rebuilt every iteration
+#[derive(Default, Debug)]
+pub struct CommunicationGraph {
+    pub agent_neighbors: HashMap<Uuid, HashSet<Uuid>>,
+    pub visible_targets: HashMap<Uuid, HashSet<Uuid>>
+}
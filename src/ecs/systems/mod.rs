@@ -2,3 +2,5 @@
 // FormFlight ECS systems
 pub mod error_system;
 pub mod simulate;
+pub mod targets;
+pub mod obstacles;
@@ -0,0 +1,22 @@
+
+use legion::*;
+use mads::ecs::resources::*;
+use mads::ecs::components::*;
+
+use crate::ecs::components::{Obstacle, ObstacleTrajectory};
+
+/// Moves Obstacles along their ObstacleTrajectory, Obstacles follow their reference kinematically
+#[system(par_for_each)]
+pub fn propagate_obstacles(
+    _obstacle: &Obstacle,
+    state: &mut FullState,
+    trajectory: &ObstacleTrajectory,
+    #[resource] time: &SimulationTime,
+    #[resource] sim_step: &EngineStep
+)
+{
+
+    let tf = time.0 + sim_step.0;
+    state.data = trajectory.motion.reference(tf, &trajectory.origin);
+
+}
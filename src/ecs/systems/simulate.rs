@@ -11,8 +11,10 @@ use mads::math::integrate::{solve_ivp, SolverOptions, IntegrateError, Integrator
 use mads::ecs::resources::*;
 use mads::ecs::components::*;
 
-use crate::ecs::resources::Assignment;
+use crate::ecs::resources::{Assignment, AgentStates, ObstacleStates, ObstacleAvoidance, ControlMode};
 use crate::ecs::components::Agent;
+use crate::avoidance::{CollisionAvoidance, safe_control};
+use crate::actuators::ActuatorState;
 
 // #[system(for_each)]
 #[system(par_for_each)]
@@ -22,11 +24,17 @@ pub fn integrate_lqr_error_dynamics<T>(
     state: &mut FullState,
     dynamics: &T,
     controller: &LQRComponent,
+    actuator: &mut ActuatorState,
     #[resource] time: &SimulationTime,
     #[resource] sim_step: &EngineStep,
     #[resource] integrator: &Integrator,
     #[resource] step: &IntegratorStep,
-    #[resource] assignment: &Assignment
+    #[resource] assignment: &Assignment,
+    #[resource] agent_states: &AgentStates,
+    #[resource] obstacle_states: &ObstacleStates,
+    #[resource] avoidance: &CollisionAvoidance,
+    #[resource] obstacle_avoidance: &ObstacleAvoidance,
+    #[resource] control_mode: &ControlMode
 ) -> Result<(), IntegrateError>
 where
     T: Component + StateSpaceRepresentation // Need to include Component trait from Legion
@@ -67,17 +75,30 @@ where
 
     };
 
-    let error_state = &x0 - &target_state;
+    // Other agents and obstacles to keep clear of, held over the step
+    let neighbors: Vec<&DVector<f32>> = agent_states.map.iter()
+        .filter(|(uuid, _neighbor)| **uuid != id.uuid)
+        .map(|(_uuid, neighbor)| neighbor)
+        .collect();
 
-    // Wrap dynamics/controls in appropriately defined closure - f(t, x)
-    let f = |t: f32, x: &DVector<f32>| {
+    // Feedback on the error state, filtered to keep clear of the other agents and obstacles
+    let control = |x: &DVector<f32>| -> DVector<f32> {
+        let error_state = x - &target_state;
         let u = -&K * &error_state;
-        dynamics.f(t, x, Some(&u))
+        if avoidance.is_enabled() || !obstacle_states.map.is_empty() {
+            let obstacles: Vec<(DVector<f32>, bool)> = obstacle_states.map.values()
+                .map(|(shape, obstacle)| shape.surface_state(obstacle, x))
+                .collect();
+            safe_control(avoidance, &obstacle_avoidance.0, &u, x, &neighbors, &obstacles)
+        } else {
+            u
+        }
     };
 
     // Integrate dynamics
     let opts = SolverOptions{ first_step: Some(step), rtol, ..SolverOptions::default() };
-    let (_times, traj) = solve_ivp(f, t_span, x0, integrator.0, opts)?;
+    let f = |t: f32, x: &DVector<f32>, u: &DVector<f32>| dynamics.f(t, x, Some(u));
+    let traj = integrate_closed_loop(f, control, actuator, *control_mode, x0, t_span, integrator.0, opts)?;
 
     // Update entity FullState component
     state.data = traj[traj.len()-1].clone();
@@ -99,3 +120,108 @@ where
     Ok(())
 
 }
+
+/// Integrates the closed loop of an Agent over a step and accumulates the statistics of the input
+/// it applied. Returns the integrated trajectory
+///  f: dynamics, state derivative given the time, state and input
+///  control: commanded input given the state
+///  actuator: input limits and statistics of the Agent
+///  control_mode: whether the limited input follows the instantaneous state or is held over the step
+#[allow(clippy::too_many_arguments)]
+pub fn integrate_closed_loop<F, C>(
+    f: F,
+    control: C,
+    actuator: &mut ActuatorState,
+    control_mode: ControlMode,
+    x0: DVector<f32>,
+    t_span: (f32, f32),
+    integrator: IntegratorType,
+    opts: SolverOptions
+) -> Result<Vec<DVector<f32>>, IntegrateError>
+where
+    F: Fn(f32, &DVector<f32>, &DVector<f32>) -> DVector<f32>,
+    C: Fn(&DVector<f32>) -> DVector<f32>
+{
+
+    match control_mode {
+
+        // Input, rate and speed limits on the input at the start of the step
+        ControlMode::ZeroOrderHold => {
+            let u0 = actuator.apply(&control(&x0), &x0, t_span.1 - t_span.0);
+            let (_times, traj) = solve_ivp(|t: f32, x: &DVector<f32>| f(t, x, &u0), t_span, x0, integrator, opts)?;
+            Ok(traj)
+        },
+
+        // Instantaneous limits on the input at every evaluation of the dynamics, rate limited from the
+        // input at the end of the previous step. Statistics are accumulated from the input applied
+        // along the integrated trajectory
+        ControlMode::Continuous => {
+            let limits = actuator.limits.clone();
+            let previous = actuator.input.clone();
+            let input = |t: f32, x: &DVector<f32>| {
+                limits.apply_instantaneous(&control(x), x, previous.as_ref(), t - t_span.0)
+            };
+            let (times, traj) = solve_ivp(|t: f32, x: &DVector<f32>| f(t, x, &input(t, x).0), t_span, x0, integrator, opts)?;
+            for k in 1..times.len().min(traj.len()) {
+                let (u, saturation) = input(times[k - 1], &traj[k - 1]);
+                actuator.record(&u, saturation, times[k] - times[k - 1]);
+            }
+
+            // Input at the end of the step, the next step is rate limited from it
+            if let Some(x) = traj.last() {
+                actuator.input = Some(input(t_span.1, x).0);
+            }
+
+            Ok(traj)
+        }
+
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actuators::ActuatorLimits;
+
+    #[test]
+    fn test_integrate_closed_loop() {
+
+        // 1-D double integrator x'' = u under u = -x - 2 x', critically damped with a double pole
+        // at -1. From (1, 0) the closed loop is x(t) = (1 + t) e^-t, x'(t) = -t e^-t
+        let f = |_t: f32, x: &DVector<f32>, u: &DVector<f32>| DVector::from_vec(vec![x[1], u[0]]);
+        let control = |x: &DVector<f32>| DVector::from_vec(vec![-x[0] - 2.0 * x[1]]);
+        let analytic = |t: f32| DVector::from_vec(vec![(1.0 + t) * (-t).exp(), -t * (-t).exp()]);
+
+        let dt = 0.1;
+        let integrate = |control_mode: ControlMode| {
+            let mut actuator = ActuatorState::new(ActuatorLimits::default());
+            let mut x = DVector::from_vec(vec![1.0, 0.0]);
+            for k in 0..20 {
+                let t0 = k as f32 * dt;
+                let opts = SolverOptions{ first_step: Some(0.01), rtol: 1E-6, ..SolverOptions::default() };
+                let traj = integrate_closed_loop(f, control, &mut actuator, control_mode, x.clone(), (t0, t0 + dt), IntegratorType::RK4, opts).unwrap();
+                x = traj[traj.len() - 1].clone();
+            }
+            (x, actuator)
+        };
+
+        // Continuous feedback follows the closed loop solution
+        let (x_continuous, actuator) = integrate(ControlMode::Continuous);
+        assert!((&x_continuous - analytic(2.0)).norm() < 1E-3);
+        assert!((actuator.total_time - 2.0).abs() < 1E-4);
+
+        // The held input follows the exact discretization, x+ = x + x' dt + u dt^2 / 2, x'+ = x' + u dt
+        let (x_held, actuator) = integrate(ControlMode::ZeroOrderHold);
+        let mut x_discrete = DVector::from_vec(vec![1.0f32, 0.0]);
+        for _ in 0..20 {
+            let u = control(&x_discrete)[0];
+            x_discrete = DVector::from_vec(vec![x_discrete[0] + x_discrete[1] * dt + u * dt.powi(2) / 2.0, x_discrete[1] + u * dt]);
+        }
+        assert!((&x_held - x_discrete).norm() < 1E-4);
+        assert!((&x_held - analytic(2.0)).norm() > 1E-2);
+        assert!((actuator.total_time - 2.0).abs() < 1E-4);
+
+    }
+
+}
@@ -0,0 +1,71 @@
+#![allow(non_snake_case)]
+
+use nalgebra::{DVector, DMatrix};
+use legion::*;
+use legion::storage::Component;
+use mads::dynamics::statespace::StateSpaceRepresentation;
+use mads::math::integrate::{solve_ivp, SolverOptions, IntegrateError};
+use mads::ecs::resources::*;
+use mads::ecs::components::*;
+
+use crate::ecs::components::{Target, TargetTrajectory};
+use crate::formation::FormationAnimation;
+
+/// Moves Targets along their TargetTrajectory, defined in the frame of the target formation
+/// Kinematic motions are set to their reference, LqrTracking motions are integrated through the
+/// Target's own dynamics and controller
+#[system(par_for_each)]
+pub fn propagate_targets<T>(
+    _target: &Target,
+    state: &mut FullState,
+    trajectory: &TargetTrajectory,
+    dynamics: &T,
+    controller: &LQRComponent,
+    #[resource] time: &SimulationTime,
+    #[resource] sim_step: &EngineStep,
+    #[resource] integrator: &Integrator,
+    #[resource] step: &IntegratorStep,
+    #[resource] formation: &FormationAnimation
+) -> Result<(), IntegrateError>
+where
+    T: Component + StateSpaceRepresentation
+{
+
+    let t0 = time.0;
+    let tf = t0 + sim_step.0;
+
+    // Reference state in the world frame
+    let reference = |t: f32| -> DVector<f32> {
+        let local = trajectory.motion.reference(t - trajectory.start_time, &trajectory.origin);
+        formation.transform_state(t, &local)
+    };
+
+    if !trajectory.motion.is_dynamic() {
+        state.data = reference(tf);
+        return Ok(());
+    }
+
+    // Solve the LQR controller
+    let (K, _P) = match controller.solve() {
+        Ok((value1, value2)) => (value1, value2),
+        Err(_) => (DMatrix::<f32>::zeros(1, 1), DMatrix::<f32>::zeros(1, 1)),
+    };
+
+    // Track the moving reference - f(t, x)
+    let f = |t: f32, x: &DVector<f32>| {
+        let error_state = x - reference(t);
+        let u = -&K * &error_state;
+        dynamics.f(t, x, Some(&u))
+    };
+
+    // Integrate dynamics
+    let x0 = state.data.clone();
+    let opts = SolverOptions{ first_step: Some(step.0), rtol: 1E-3, ..SolverOptions::default() };
+    let (_times, traj) = solve_ivp(f, (t0, tf), x0, integrator.0, opts)?;
+
+    // Update entity FullState component
+    state.data = traj[traj.len()-1].clone();
+
+    Ok(())
+
+}
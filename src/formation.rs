@@ -0,0 +1,170 @@
+
+use nalgebra::{DVector, Vector3, UnitQuaternion};
+
+/// Pose of a formation. Formation slots are scaled, rotated and then translated
+#[derive(Clone, Debug, PartialEq)]
+pub struct FormationPose {
+    pub translation: Vector3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+    pub scale: f32
+}
+
+impl FormationPose {
+
+    pub fn new(translation: Vector3<f32>, rotation: UnitQuaternion<f32>, scale: f32) -> Self {
+        Self { translation, rotation, scale }
+    }
+
+    /// Formation placed at a given position without rotation or scaling
+    pub fn from_translation(translation: Vector3<f32>) -> Self {
+        Self { translation, rotation: UnitQuaternion::identity(), scale: 1f32 }
+    }
+
+    /// Maps a point from the formation frame to the world frame
+    pub fn transform_point(&self, point: &Vector3<f32>) -> Vector3<f32> {
+        self.translation + self.rotation * (point * self.scale)
+    }
+
+    /// Maps the points of a Distribution from the formation frame to the world frame
+    pub fn transform(&self, points: &[Vector3<f32>]) -> Vec<Vector3<f32>> {
+        points.iter().map(|point| self.transform_point(point)).collect()
+    }
+
+}
+
+impl Default for FormationPose {
+
+    fn default() -> Self {
+        Self::from_translation(Vector3::zeros())
+    }
+
+}
+
+/// Formation pose moving over time, e.g. a formation that migrates, rotates or grows
+#[derive(Clone, Debug, PartialEq)]
+pub struct FormationAnimation {
+
+    /// Pose at the initial time
+    pub initial: FormationPose,
+
+    /// Translational velocity of the formation origin
+    pub velocity: Vector3<f32>,
+
+    /// Angular velocity of the formation about its origin (rad/s, world frame)
+    pub angular_velocity: Vector3<f32>,
+
+    /// Rate of change of the formation scale
+    pub scale_rate: f32,
+
+    /// Simulation time at which the formation holds its initial pose
+    pub start_time: f32
+
+}
+
+impl FormationAnimation {
+
+    pub fn new(initial: FormationPose, velocity: Vector3<f32>, angular_velocity: Vector3<f32>, scale_rate: f32) -> Self {
+        Self { initial, velocity, angular_velocity, scale_rate, start_time: 0f32 }
+    }
+
+    /// Formation that holds a fixed pose
+    pub fn stationary(pose: FormationPose) -> Self {
+        Self::new(pose, Vector3::zeros(), Vector3::zeros(), 0f32)
+    }
+
+    /// Same animation starting at a given simulation time
+    pub fn starting_at(mut self, start_time: f32) -> Self {
+        self.start_time = start_time;
+        self
+    }
+
+    /// Pose of the formation at time t
+    pub fn pose(&self, t: f32) -> FormationPose {
+
+        let t = t - self.start_time;
+
+        let translation = self.initial.translation + self.velocity * t;
+        let rotation = UnitQuaternion::from_scaled_axis(self.angular_velocity * t) * self.initial.rotation;
+        let scale = (self.initial.scale + self.scale_rate * t).max(0f32);
+
+        FormationPose { translation, rotation, scale }
+
+    }
+
+    /// Maps a [position, velocity] state from the formation frame to the world frame at time t
+    /// States with 2 spatial dimensions are treated as lying in the z = 0 plane
+    pub fn transform_state(&self, t: f32, local: &DVector<f32>) -> DVector<f32> {
+
+        let dim = local.len() / 2;
+        let pose = self.pose(t);
+
+        let mut position = Vector3::<f32>::zeros();
+        let mut velocity = Vector3::<f32>::zeros();
+        for i in 0..dim.min(3) {
+            position[i] = local[i];
+            velocity[i] = local[dim + i];
+        }
+
+        // p' = T + s R p
+        // v' = dT/dt + w x (s R p) + ds/dt R p + s R v
+        let rotated = pose.rotation * position;
+        let world_position = pose.translation + rotated * pose.scale;
+        let world_velocity = self.velocity
+            + self.angular_velocity.cross(&(rotated * pose.scale))
+            + rotated * self.scale_rate
+            + pose.rotation * velocity * pose.scale;
+
+        let mut state = local.clone();
+        for i in 0..dim.min(3) {
+            state[i] = world_position[i];
+            state[dim + i] = world_velocity[i];
+        }
+
+        state
+
+    }
+
+}
+
+impl Default for FormationAnimation {
+
+    fn default() -> Self {
+        Self::stationary(FormationPose::default())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transform_state() {
+
+        // quarter turn about z, doubled and moved
+        let pose = FormationPose::new(
+            Vector3::new(1.0, 2.0, 3.0),
+            UnitQuaternion::from_scaled_axis(Vector3::new(0.0, 0.0, std::f32::consts::FRAC_PI_2)),
+            2.0
+        );
+        assert!((pose.transform_point(&Vector3::new(1.0, 0.0, 0.0)) - Vector3::new(1.0, 4.0, 3.0)).norm() < 1E-5);
+
+        // p' = T + s R p, v' = dT/dt + w x (s R p) + ds/dt R p + s R v
+        let animation = FormationAnimation::new(pose, Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.5), 0.1);
+        let local = DVector::from_vec(vec![1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        let state = animation.transform_state(0.0, &local);
+        let truth = DVector::from_vec(vec![1.0, 4.0, 3.0, -2.0, 0.1, 0.0]);
+        assert!((state - truth).norm() < 1E-5);
+
+        // the velocity of a fixed formation slot is the derivative of its position
+        let slot = DVector::from_vec(vec![1.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        let h = 1E-3;
+        let state = animation.transform_state(1.0, &slot);
+        let ahead = animation.transform_state(1.0 + h, &slot);
+        let behind = animation.transform_state(1.0 - h, &slot);
+        let derivative = (ahead.rows(0, 3) - behind.rows(0, 3)) / (2.0 * h);
+        assert!((derivative - state.rows(3, 3)).norm() < 1E-2);
+
+    }
+
+}
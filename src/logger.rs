@@ -4,15 +4,22 @@ use std::io::BufWriter;
 use std::error::Error;
 use std::collections::HashMap;
 use legion::*;
+use nalgebra::DVector;
+use uuid::Uuid;
 
 // MADS
 use mads::ecs::components::{SimID, FullState};
 use mads::simulator::state::SimulatorState;
+use mads::ecs::resources::{SimulationTimeHistory, SimulationResult};
 use mads::log::Logger;
 
 // formflight
 use crate::ecs::resources::AssignmentHistory;
-use crate::ecs::components::{Agent, Target};
+use crate::ecs::components::{Agent, Target, Obstacle};
+use crate::mission::MissionLog;
+use crate::obstacles::{ObstacleShape, ObstacleRecord};
+use crate::safety::{SafetyConfig, SafetyMonitor};
+use crate::actuators::ActuatorState;
 
 pub struct FormFlightLogger;
 
@@ -47,6 +54,7 @@ impl FormFlightLogger {
 
         let mut agent_id_query = <(&SimID, &FullState, &Agent)>::query();
         let mut target_id_query = <(&SimID, &FullState, &Target)>::query();
+        let mut obstacle_id_query = <(&SimID, &Obstacle)>::query();
 
         // let mut entities = HashMap::<String, Vec<(&SimID, &StateSpace)>>::new();
         let mut entities = HashMap::<String, Vec<&SimID>>::new();
@@ -61,6 +69,10 @@ impl FormFlightLogger {
             entities.entry("Targets".to_string()).or_insert(Vec::new()).push( &id );
         }
 
+        for (id, _obstacle) in obstacle_id_query.iter(&sim_state.ecs.world) {
+            entities.entry("Obstacles".to_string()).or_insert(Vec::new()).push( &id );
+        }
+
         // Serialize to JSON
         let entities_json = serde_json::to_string_pretty(&entities)?;
 
@@ -73,6 +85,180 @@ impl FormFlightLogger {
 
     }
 
+    /// Write Obstacle names and shapes to JSON
+    pub fn obstacles_to_json(&self, sim_state: &SimulatorState, filepath: &str) -> serde_json::Result<()> {
+
+        // Scenarios without obstacles have nothing to write
+        let obstacles = obstacle_records(sim_state);
+        if obstacles.is_empty() {
+            return Ok(());
+        }
+
+        let f = fs::File::create(filepath).expect("Unable to create file");
+        let bw = BufWriter::new(f);
+
+        // Serialize obstacle shapes to JSON
+        let j = serde_json::to_string_pretty(&obstacles)?;
+
+        serde_json::to_writer(bw, &j).expect("Failed writing : (");
+
+        Ok(())
+
+    }
+
+    /// Write the collision and near-miss summary of a run to JSON
+    pub fn safety_to_json(&self, monitor: &SafetyMonitor, filepath: &str) -> serde_json::Result<()> {
+
+        let f = fs::File::create(filepath).expect("Unable to create file");
+        let bw = BufWriter::new(f);
+
+        // Serialize safety metrics to JSON
+        let j = serde_json::to_string_pretty(monitor)?;
+
+        serde_json::to_writer(bw, &j).expect("Failed writing : (");
+
+        Ok(())
+
+    }
+
+    /// Write the control effort, peak input and time fraction spent at each actuator limit of every
+    /// Agent to CSV
+    pub fn actuators_to_csv(&self, sim_state: &SimulatorState, filepath: &str) -> Result<(), Box<dyn Error>> {
+
+        let mut writer = csv::Writer::from_path(filepath)?;
+        writer.write_record(&["agent", "effort", "peak_input", "input_saturation", "rate_limited", "speed_limited"])?;
+
+        let mut query = <(&SimID, &ActuatorState, &Agent)>::query();
+        let mut actuators: Vec<(&SimID, &ActuatorState)> = query.iter(&sim_state.ecs.world)
+            .map(|(id, actuator, _agent)| (id, actuator))
+            .collect();
+        actuators.sort_by(|(id1, _), (id2, _)| id1.name.cmp(&id2.name));
+
+        for (id, actuator) in actuators {
+            let (input_saturation, rate_limited, speed_limited) = actuator.saturation_fractions();
+            writer.write_record(&[
+                id.name.clone(),
+                actuator.effort.to_string(),
+                actuator.peak_input.to_string(),
+                input_saturation.to_string(),
+                rate_limited.to_string(),
+                speed_limited.to_string()
+            ])?;
+        }
+        writer.flush()?;
+
+        Ok(())
+
+    }
+
+    /// Write mission phase transitions and per-phase metrics to JSON
+    pub fn mission_to_json(&self, sim_state: &SimulatorState, filepath: &str) -> serde_json::Result<()> {
+
+        // Scenarios without a mission plan have nothing to write
+        let mission_log = match sim_state.ecs.resources.get::<MissionLog>() {
+            Some(mission_log) => mission_log,
+            None => return Ok(())
+        };
+
+        if mission_log.phases.is_empty() {
+            return Ok(());
+        }
+
+        let f = fs::File::create(filepath).expect("Unable to create file");
+        let bw = BufWriter::new(f);
+
+        // Serialize phase records to JSON
+        let j = serde_json::to_string_pretty(&mission_log.phases)?;
+
+        serde_json::to_writer(bw, &j).expect("Failed writing : (");
+
+        Ok(())
+
+    }
+
+}
+
+/// Names and shapes of the Obstacles in a simulation
+pub fn obstacle_records(sim_state: &SimulatorState) -> Vec<ObstacleRecord> {
+
+    let mut query = <(&SimID, &ObstacleShape, &Obstacle)>::query();
+    query.iter(&sim_state.ecs.world)
+        .map(|(id, shape, _obstacle)| ObstacleRecord { name: id.name.clone(), shape: shape.clone() })
+        .collect()
+
+}
+
+/// Names of the Agents in a simulation
+pub fn agent_names(sim_state: &SimulatorState) -> Vec<String> {
+
+    let mut query = <(&SimID, &Agent)>::query();
+    query.iter(&sim_state.ecs.world)
+        .map(|(id, _agent)| id.name.clone())
+        .collect()
+
+}
+
+/// Collision and near-miss metrics recorded during a simulation, computed from its stored
+/// trajectories when the simulation did not record them
+pub fn safety_monitor(sim_state: &SimulatorState) -> SafetyMonitor {
+
+    if let Some(monitor) = sim_state.ecs.resources.get::<SafetyMonitor>() {
+        return monitor.clone();
+    }
+
+    let time_history = sim_state.ecs.resources.get::<SimulationTimeHistory>().unwrap();
+    let result = sim_state.ecs.resources.get::<SimulationResult>().unwrap();
+
+    SafetyMonitor::from_results(SafetyConfig::default(), &time_history, &result, &agent_names(sim_state), &obstacle_records(sim_state))
+
+}
+
+/// Reads the Obstacle shapes written by FormFlightLogger::obstacles_to_json
+pub fn read_obstacles_json(filepath: &str) -> Result<Vec<ObstacleRecord>, Box<dyn Error>> {
+
+    // Stored as a JSON encoded string
+    let contents = fs::read_to_string(filepath)?;
+    let j: String = serde_json::from_str(&contents)?;
+
+    Ok(serde_json::from_str(&j)?)
+
+}
+
+/// Reads the Agent/Target trajectories written to CSV by FormFlightLogger::to_csv
+/// Each entity spans one column per state component, under a header repeating its name
+pub fn read_results_csv(filepath: &str) -> Result<SimulationResult, Box<dyn Error>> {
+
+    let mut reader = csv::Reader::from_path(filepath)?;
+    let headers: Vec<String> = reader.headers()?.iter().map(|name| name.to_string()).collect();
+
+    // Consecutive columns of each entity
+    let mut entities: Vec<(String, Vec<usize>)> = Vec::new();
+    for (column, name) in headers.iter().enumerate() {
+        if name.eq_ignore_ascii_case("time") {
+            continue;
+        }
+        match entities.last_mut() {
+            Some((last, columns)) if last == name => columns.push(column),
+            _ => entities.push((name.clone(), vec![column]))
+        }
+    }
+
+    let mut result = SimulationResult { data: HashMap::new() };
+    let mut trajectories: Vec<Vec<FullState>> = vec![Vec::new(); entities.len()];
+    for record in reader.records() {
+        let record = record?;
+        for (i, (_name, columns)) in entities.iter().enumerate() {
+            let state: Result<Vec<f32>, _> = columns.iter().map(|column| record[*column].trim().parse::<f32>()).collect();
+            trajectories[i].push(FullState { data: DVector::from_vec(state?) });
+        }
+    }
+
+    for ((name, _columns), trajectory) in entities.into_iter().zip(trajectories) {
+        result.data.insert(SimID { uuid: Uuid::new_v4(), name }, trajectory);
+    }
+
+    Ok(result)
+
 }
 
 // Implement Logger for FormFlightLogger and use default to_csv() function
@@ -81,3 +267,53 @@ impl Logger for FormFlightLogger {
 }
 
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mads::simulator::Simulator;
+    use mads::log::LogDataType;
+    use crate::config::{ScenarioConfig, EngineSettings, IntegratorConfig};
+
+    #[test]
+    fn test_read_results_csv() {
+
+        let mut config = ScenarioConfig::default();
+        config.agents.count = 2;
+        config.targets.count = 2;
+        config.engine = Some(EngineSettings {
+            start_time: 0.0,
+            max_time: 1.0,
+            step: 0.1,
+            integrator: IntegratorConfig::RK4,
+            integrator_step: 0.1
+        });
+
+        let sim_state = SimulatorState::new(config.engine_config(), config.simulator_config());
+        let mut simulator = Simulator::new(sim_state, config.scenario().unwrap());
+        simulator.build();
+        simulator.run();
+
+        // Trajectories written by to_csv are read back under the same names
+        let filepath = std::env::temp_dir().join("formflight_test_results.csv");
+        let filepath = filepath.to_string_lossy();
+        FormFlightLogger.to_csv(simulator.get_state(), &filepath, LogDataType::SimResult).unwrap();
+        let data = read_results_csv(&filepath).unwrap();
+
+        let result = simulator.get_state().ecs.resources.get::<SimulationResult>().unwrap();
+        assert_eq!(data.data.len(), result.data.len());
+        for (id, trajectory) in result.data.iter() {
+
+            let (_read_id, read_trajectory) = data.data.iter()
+                .find(|(read_id, _)| read_id.name == id.name)
+                .unwrap();
+
+            assert_eq!(read_trajectory.len(), trajectory.len());
+            for (read_state, state) in read_trajectory.iter().zip(trajectory.iter()) {
+                assert!((&read_state.data - &state.data).norm() < 1E-3);
+            }
+
+        }
+
+    }
+
+}
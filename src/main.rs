@@ -5,38 +5,34 @@ extern crate rust_optimal_transport as rot;
 
 pub mod plot;
 pub mod distributions;
+pub mod point_cloud;
+pub mod formation;
 pub mod tracking_scenario;
 pub mod logger;
 pub mod assignments;
+pub mod communication;
+pub mod avoidance;
+pub mod obstacles;
+pub mod safety;
+pub mod actuators;
+pub mod motion;
+pub mod mission;
 pub mod ecs;
 pub mod post_process;
+pub mod config;
+pub mod cli;
+pub mod batch;
 
-// MADS
-use mads::simulator::configuration::{EngineConfig, SimulatorConfig};
-use mads::simulator::Simulator;
-use mads::simulator::state::SimulatorState;
+use clap::Parser;
 
 // formflight
-use crate::tracking_scenario::TrackingScenario;
-use crate::post_process::*;
+use crate::cli::{Cli, execute};
 
 fn main() {
 
-    // Configure MADS simulator
-    let engine_config = EngineConfig::default();
-    let sim_config = SimulatorConfig::default();
-    let sim_state = SimulatorState::new(engine_config, sim_config);
-
-    // Configure Scenario
-    let scenario = TrackingScenario::default();
-
-    // Simulate
-    let mut simulator = Simulator::new(sim_state, scenario);
-    simulator.build();
-    simulator.run();
-
-    // Post-Process
-    post_process(&simulator);
-
- }
+    if let Err(err) = execute(Cli::parse()) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
 
+}
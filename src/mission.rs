@@ -0,0 +1,246 @@
+
+use serde::Serialize;
+
+use crate::distributions::Distribution;
+use crate::formation::FormationAnimation;
+
+/// Condition under which a mission phase ends and the next phase begins
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransitionCondition {
+
+    /// Time spent in the phase
+    TimeElapsed(f32),
+
+    /// Every assigned agent is within a position tolerance of its target. Unassigned agents, e.g.
+    /// when agents outnumber targets, are ignored
+    AllWithin(f32),
+
+    /// A fraction of the agents is within a position tolerance of their assigned targets
+    FractionConverged { fraction: f32, tolerance: f32 }
+
+}
+
+impl TransitionCondition {
+
+    /// Whether the phase is complete
+    ///  elapsed: time spent in the phase
+    ///  errors: position error of each agent to its assigned target, None if unassigned
+    pub fn is_met(&self, elapsed: f32, errors: &[Option<f32>]) -> bool {
+
+        match self {
+            TransitionCondition::TimeElapsed(duration) => elapsed >= *duration,
+            TransitionCondition::AllWithin(tolerance) => {
+                let mut assigned = errors.iter().filter_map(|error| *error).peekable();
+                assigned.peek().is_some() && assigned.all(|error| error <= *tolerance)
+            },
+            TransitionCondition::FractionConverged { fraction, tolerance } => {
+                converged_fraction(errors, *tolerance) >= *fraction
+            }
+        }
+
+    }
+
+}
+
+/// Target formation held until its transition condition is met
+#[derive(Clone, Debug)]
+pub struct MissionPhase {
+    pub formation: Distribution,
+    pub pose: FormationAnimation,
+    pub transition: TransitionCondition
+}
+
+impl MissionPhase {
+
+    pub fn new(formation: Distribution, pose: FormationAnimation, transition: TransitionCondition) -> Self {
+        Self { formation, pose, transition }
+    }
+
+}
+
+/// Sequence of target formations the agents move through, e.g. formation A -> B -> C
+#[derive(Clone, Debug)]
+pub struct MissionPlan {
+
+    pub phases: Vec<MissionPhase>,
+
+    /// Position tolerance used to report converged agents in the MissionLog
+    pub tolerance: f32,
+
+    // Index of the active phase
+    current: usize
+
+}
+
+impl MissionPlan {
+
+    pub fn new(phases: Vec<MissionPhase>) -> Self {
+        Self { phases, tolerance: 1f32, current: 0 }
+    }
+
+    /// Index of the active phase
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    /// The active phase, if any
+    pub fn current(&self) -> Option<&MissionPhase> {
+        self.phases.get(self.current)
+    }
+
+    /// Moves to the next phase and returns it. The last phase is held once reached
+    pub fn advance(&mut self) -> Option<&MissionPhase> {
+
+        if self.current + 1 < self.phases.len() {
+            self.current += 1;
+            self.phases.get(self.current)
+        } else {
+            None
+        }
+
+    }
+
+}
+
+/// Metrics recorded for a single mission phase
+#[derive(Clone, Debug, Serialize)]
+pub struct PhaseRecord {
+    pub phase: usize,
+    pub start_time: f32,
+    pub end_time: Option<f32>,
+    pub mean_error: f32,
+    pub max_error: f32,
+    pub converged_fraction: f32
+}
+
+/// Phase transitions and per-phase metrics over a mission
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct MissionLog {
+
+    pub phases: Vec<PhaseRecord>,
+
+    /// Position tolerance used for the converged fraction
+    pub tolerance: f32
+
+}
+
+impl MissionLog {
+
+    pub fn new(tolerance: f32) -> Self {
+        Self { phases: Vec::new(), tolerance }
+    }
+
+    /// Opens the record of a phase
+    pub fn begin(&mut self, phase: usize, time: f32) {
+
+        self.phases.push(PhaseRecord {
+            phase,
+            start_time: time,
+            end_time: None,
+            mean_error: 0f32,
+            max_error: 0f32,
+            converged_fraction: 0f32
+        });
+
+    }
+
+    /// Updates the metrics of the active phase
+    pub fn update(&mut self, errors: &[Option<f32>]) {
+
+        let tolerance = self.tolerance;
+        if let Some(record) = self.phases.last_mut() {
+
+            let assigned: Vec<f32> = errors.iter().filter_map(|error| *error).collect();
+            if !assigned.is_empty() {
+                record.mean_error = assigned.iter().sum::<f32>() / assigned.len() as f32;
+                record.max_error = assigned.iter().cloned().fold(0f32, f32::max);
+            }
+            record.converged_fraction = converged_fraction(errors, tolerance);
+
+        }
+
+    }
+
+    /// Closes the record of the active phase
+    pub fn end(&mut self, time: f32) {
+
+        if let Some(record) = self.phases.last_mut() {
+            record.end_time = Some(time);
+        }
+
+    }
+
+}
+
+/// Fraction of agents within a position tolerance of their assigned targets
+pub fn converged_fraction(errors: &[Option<f32>], tolerance: f32) -> f32 {
+
+    if errors.is_empty() {
+        return 0f32;
+    }
+
+    let converged = errors.iter()
+        .filter(|error| matches!(error, Some(e) if *e <= tolerance))
+        .count();
+
+    converged as f32 / errors.len() as f32
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transition_conditions() {
+
+        // more agents than targets, the unassigned agent does not hold the phase back
+        let errors = [Some(0.5), Some(0.2), None];
+
+        assert!(TransitionCondition::AllWithin(1.0).is_met(0.0, &errors));
+        assert!(!TransitionCondition::AllWithin(0.3).is_met(0.0, &errors));
+        assert!(!TransitionCondition::AllWithin(1.0).is_met(0.0, &[None, None]));
+
+        // fractions are taken over every agent
+        assert!(TransitionCondition::FractionConverged { fraction: 0.6, tolerance: 1.0 }.is_met(0.0, &errors));
+        assert!(!TransitionCondition::FractionConverged { fraction: 0.7, tolerance: 1.0 }.is_met(0.0, &errors));
+
+        assert!(!TransitionCondition::TimeElapsed(2.0).is_met(1.9, &errors));
+        assert!(TransitionCondition::TimeElapsed(2.0).is_met(2.0, &errors));
+
+    }
+
+    #[test]
+    fn test_mission_plan() {
+
+        let phase = |radius: f32| {
+            MissionPhase::new(Distribution::Circle3D { radius }, FormationAnimation::default(), TransitionCondition::TimeElapsed(1.0))
+        };
+        let mut mission = MissionPlan::new(vec![phase(1.0), phase(2.0)]);
+
+        assert_eq!(mission.current_index(), 0);
+        assert_eq!(mission.advance().map(|phase| phase.formation.clone()), Some(Distribution::Circle3D { radius: 2.0 }));
+
+        // the last phase is held
+        assert!(mission.advance().is_none());
+        assert_eq!(mission.current_index(), 1);
+        assert!(mission.current().is_some());
+
+        // phase metrics
+        let mut log = MissionLog::new(1.0);
+        log.begin(0, 0.0);
+        log.update(&[Some(0.5), Some(3.0), None]);
+        log.end(2.0);
+        log.begin(1, 2.0);
+
+        let record = &log.phases[0];
+        assert_eq!(record.end_time, Some(2.0));
+        assert_eq!(record.mean_error, 1.75);
+        assert_eq!(record.max_error, 3.0);
+        assert!((record.converged_fraction - 1.0 / 3.0).abs() < 1E-6);
+        assert_eq!(log.phases[1].end_time, None);
+
+    }
+
+}
+
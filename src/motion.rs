@@ -0,0 +1,197 @@
+
+use std::fmt;
+use std::sync::Arc;
+use nalgebra::{DVector, Vector3};
+
+/// Time-parameterized state of a Target given the time and its initial state
+pub type ScriptedTrajectory = Arc<dyn Fn(f32, &DVector<f32>) -> DVector<f32> + Send + Sync>;
+
+/// Motion model of a Target
+///
+/// States are [position, velocity] with the motion evaluated over the first position/velocity
+/// components, e.g. [x, y, z, vx, vy, vz].
+#[derive(Clone, Default)]
+pub enum TargetMotion {
+
+    /// Holds its initial state
+    #[default]
+    Stationary,
+
+    /// Moves from its initial position at a constant velocity
+    ConstantVelocity(Vector3<f32>),
+
+    /// Follows straight line segments through waypoints, given relative to the initial position,
+    /// at a constant speed. Cyclic paths return to the initial position and repeat
+    Waypoints { waypoints: Vec<Vector3<f32>>, speed: f32, cyclic: bool },
+
+    /// Arbitrary time-parameterized trajectory
+    Scripted(ScriptedTrajectory),
+
+    /// Target dynamics driven by its own LQR controller to track the reference of another motion
+    LqrTracking(Box<TargetMotion>)
+
+}
+
+impl TargetMotion {
+
+    /// Reference state at time t of a Target that started at origin
+    pub fn reference(&self, t: f32, origin: &DVector<f32>) -> DVector<f32> {
+
+        let dim = origin.len() / 2;
+
+        match self {
+
+            TargetMotion::Stationary => origin.clone(),
+
+            TargetMotion::ConstantVelocity(velocity) => {
+                let mut state = origin.clone();
+                for i in 0..dim {
+                    state[i] = origin[i] + velocity[i] * t;
+                    state[dim + i] = velocity[i];
+                }
+                state
+            },
+
+            TargetMotion::Waypoints { waypoints, speed, cyclic } => {
+                let (position, velocity) = waypoint_reference(waypoints, *speed, *cyclic, t);
+                let mut state = origin.clone();
+                for i in 0..dim {
+                    state[i] = origin[i] + position[i];
+                    state[dim + i] = velocity[i];
+                }
+                state
+            },
+
+            TargetMotion::Scripted(trajectory) => trajectory(t, origin),
+
+            TargetMotion::LqrTracking(motion) => motion.reference(t, origin)
+
+        }
+
+    }
+
+    /// Whether the Target state is integrated through its dynamics rather than set to the reference
+    pub fn is_dynamic(&self) -> bool {
+        matches!(self, TargetMotion::LqrTracking(_))
+    }
+
+}
+
+impl fmt::Debug for TargetMotion {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargetMotion::Stationary => write!(f, "Stationary"),
+            TargetMotion::ConstantVelocity(velocity) => f.debug_tuple("ConstantVelocity").field(velocity).finish(),
+            TargetMotion::Waypoints { waypoints, speed, cyclic } => {
+                f.debug_struct("Waypoints")
+                    .field("waypoints", waypoints)
+                    .field("speed", speed)
+                    .field("cyclic", cyclic)
+                    .finish()
+            },
+            TargetMotion::Scripted(_) => write!(f, "Scripted"),
+            TargetMotion::LqrTracking(motion) => f.debug_tuple("LqrTracking").field(motion).finish()
+        }
+    }
+
+}
+
+/// Position offset and velocity along a waypoint path starting at the origin after time t
+fn waypoint_reference(waypoints: &[Vector3<f32>], speed: f32, cyclic: bool, t: f32) -> (Vector3<f32>, Vector3<f32>) {
+
+    // Path vertices relative to the initial position
+    let mut path = vec![Vector3::<f32>::zeros()];
+    path.extend_from_slice(waypoints);
+    if cyclic {
+        path.push(Vector3::<f32>::zeros());
+    }
+
+    let length: f32 = path.windows(2).map(|segment| (segment[1] - segment[0]).norm()).sum();
+    if length <= 0f32 || speed <= 0f32 {
+        return (Vector3::<f32>::zeros(), Vector3::<f32>::zeros());
+    }
+
+    let mut distance = speed * t.max(0f32);
+    if cyclic {
+        distance %= length;
+    } else if distance >= length {
+        return (path[path.len() - 1], Vector3::<f32>::zeros());
+    }
+
+    for segment in path.windows(2) {
+        let delta = segment[1] - segment[0];
+        let segment_length = delta.norm();
+        if distance <= segment_length && segment_length > 0f32 {
+            let direction = delta / segment_length;
+            return (segment[0] + direction * distance, direction * speed);
+        }
+        distance -= segment_length;
+    }
+
+    (path[path.len() - 1], Vector3::<f32>::zeros())
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path() -> Vec<Vector3<f32>> {
+        vec![Vector3::new(10.0, 0.0, 0.0), Vector3::new(10.0, 10.0, 0.0)]
+    }
+
+    #[test]
+    fn test_waypoint_reference() {
+
+        // interpolates along each segment at the given speed
+        let (position, velocity) = waypoint_reference(&path(), 2.0, false, 2.5);
+        assert!((position - Vector3::new(5.0, 0.0, 0.0)).norm() < 1E-5);
+        assert!((velocity - Vector3::new(2.0, 0.0, 0.0)).norm() < 1E-5);
+
+        let (position, velocity) = waypoint_reference(&path(), 2.0, false, 7.5);
+        assert!((position - Vector3::new(10.0, 5.0, 0.0)).norm() < 1E-5);
+        assert!((velocity - Vector3::new(0.0, 2.0, 0.0)).norm() < 1E-5);
+
+        // holds the final waypoint at rest
+        let (position, velocity) = waypoint_reference(&path(), 2.0, false, 100.0);
+        assert_eq!(position, Vector3::new(10.0, 10.0, 0.0));
+        assert_eq!(velocity, Vector3::zeros());
+
+        // cyclic paths return to the start and repeat
+        let length = 20.0 + 200f32.sqrt();
+        let (position, velocity) = waypoint_reference(&path(), 2.0, true, (length + 5.0) / 2.0);
+        assert!((position - Vector3::new(5.0, 0.0, 0.0)).norm() < 1E-4);
+        assert!((velocity - Vector3::new(2.0, 0.0, 0.0)).norm() < 1E-5);
+
+        let (position, velocity) = waypoint_reference(&path(), 2.0, true, (length - 1.0) / 2.0);
+        let back = Vector3::new(-1.0, -1.0, 0.0) / 2f32.sqrt();
+        assert!((position + back).norm() < 1E-4);
+        assert!((velocity - back * 2.0).norm() < 1E-5);
+
+    }
+
+    #[test]
+    fn test_target_motion_reference() {
+
+        let origin = DVector::from_vec(vec![1.0, 2.0, 3.0, 0.0, 0.0, 0.0]);
+
+        assert_eq!(TargetMotion::Stationary.reference(5.0, &origin), origin);
+
+        let motion = TargetMotion::ConstantVelocity(Vector3::new(1.0, 0.0, -1.0));
+        assert_eq!(motion.reference(2.0, &origin), DVector::from_vec(vec![3.0, 2.0, 1.0, 1.0, 0.0, -1.0]));
+
+        // waypoints are relative to the initial position, LQR tracking follows the same reference
+        let motion = TargetMotion::Waypoints { waypoints: path(), speed: 2.0, cyclic: false };
+        let truth = DVector::from_vec(vec![6.0, 2.0, 3.0, 2.0, 0.0, 0.0]);
+        assert!((motion.reference(2.5, &origin) - &truth).norm() < 1E-5);
+        assert!((TargetMotion::LqrTracking(Box::new(motion)).reference(2.5, &origin) - truth).norm() < 1E-5);
+
+        // planar states only use the x, y components
+        let planar = DVector::from_vec(vec![1.0, 2.0, 0.0, 0.0]);
+        let motion = TargetMotion::ConstantVelocity(Vector3::new(1.0, 1.0, 1.0));
+        assert_eq!(motion.reference(1.0, &planar), DVector::from_vec(vec![2.0, 3.0, 1.0, 1.0]));
+
+    }
+
+}
@@ -0,0 +1,357 @@
+
+use nalgebra::{DVector, Vector3};
+use serde::{Serialize, Deserialize};
+
+use crate::motion::TargetMotion;
+use crate::tracking_scenario::position;
+
+/// Geometry of an obstacle about its center
+/// Planar scenarios use the cross-section of the shape with the z = 0 plane
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ObstacleShape {
+
+    Sphere { radius: f32 },
+
+    /// Axis-aligned box
+    Box { half_extents: Vector3<f32> },
+
+    /// Cylinder with its axis along z
+    Cylinder { radius: f32, half_height: f32 }
+
+}
+
+// Iterations of the search for the point of a segment closest to an obstacle
+const SEGMENT_SEARCH_ITER: usize = 60;
+
+// Number of vertices used to draw round outlines
+const OUTLINE_RESOLUTION: usize = 32;
+
+impl ObstacleShape {
+
+    /// Point of the obstacle closest to a given point, the point itself when inside the obstacle
+    ///  center: center of the obstacle
+    pub fn closest_point(&self, center: &Vector3<f32>, point: &Vector3<f32>) -> Vector3<f32> {
+
+        let offset = point - center;
+
+        match self {
+
+            ObstacleShape::Sphere { radius } => {
+                let distance = offset.norm();
+                if distance <= *radius {
+                    *point
+                } else {
+                    center + offset * (radius / distance)
+                }
+            },
+
+            ObstacleShape::Box { half_extents } => {
+                center + Vector3::new(
+                    offset.x.clamp(-half_extents.x, half_extents.x),
+                    offset.y.clamp(-half_extents.y, half_extents.y),
+                    offset.z.clamp(-half_extents.z, half_extents.z)
+                )
+            },
+
+            ObstacleShape::Cylinder { radius, half_height } => {
+                let radial = (offset.x.powi(2) + offset.y.powi(2)).sqrt();
+                let scale = if radial <= *radius { 1f32 } else { radius / radial };
+                center + Vector3::new(offset.x * scale, offset.y * scale, offset.z.clamp(-half_height, *half_height))
+            }
+
+        }
+
+    }
+
+    /// Distance from a point to the obstacle, zero inside the obstacle
+    pub fn distance(&self, center: &Vector3<f32>, point: &Vector3<f32>) -> f32 {
+        (point - self.closest_point(center, point)).norm()
+    }
+
+    /// Smallest distance from the segment [start, end] to the obstacle
+    pub fn segment_distance(&self, center: &Vector3<f32>, start: &Vector3<f32>, end: &Vector3<f32>) -> f32 {
+
+        // The distance to a convex shape is convex along the segment, ternary search for its minimum
+        let distance = |s: f32| self.distance(center, &(start + (end - start) * s));
+
+        let (mut low, mut high) = (0f32, 1f32);
+        let mut closest = distance(0f32).min(distance(1f32));
+        for _ in 0..SEGMENT_SEARCH_ITER {
+            let s1 = low + (high - low) / 3f32;
+            let s2 = high - (high - low) / 3f32;
+            let (d1, d2) = (distance(s1), distance(s2));
+            closest = closest.min(d1).min(d2);
+            if d1 <= d2 {
+                high = s2;
+            } else {
+                low = s1;
+            }
+        }
+
+        closest
+
+    }
+
+    /// Whether the segment [start, end] passes within a margin of the obstacle
+    pub fn blocks(&self, center: &Vector3<f32>, start: &Vector3<f32>, end: &Vector3<f32>, margin: f32) -> bool {
+        self.segment_distance(center, start, end) <= margin
+    }
+
+    /// Whether a point lies inside the obstacle
+    ///  center: center of the obstacle
+    pub fn contains(&self, center: &Vector3<f32>, point: &Vector3<f32>) -> bool {
+        self.closest_point(center, point) == *point
+    }
+
+    /// Point of the obstacle surface closest to a point inside the obstacle, reached by moving along
+    /// the first axes coordinates only
+    ///  center: center of the obstacle
+    ///  axes: number of coordinates the point may move along, 2 for planar scenarios
+    fn exit_point(&self, center: &Vector3<f32>, point: &Vector3<f32>, axes: usize) -> Vector3<f32> {
+
+        let offset = point - center;
+        let mut exit = offset;
+
+        match self {
+
+            // Out along the free coordinates, on the cross-section through the fixed ones
+            ObstacleShape::Sphere { radius } => {
+                let free = Vector3::from_fn(|i, _| if i < axes { offset[i] } else { 0f32 });
+                let fixed = offset - free;
+                let section = (radius.powi(2) - fixed.norm_squared()).max(0f32).sqrt();
+                let direction = if free.norm() > 0f32 { free.normalize() } else { Vector3::x() };
+                exit = fixed + direction * section;
+            },
+
+            // Through the nearest face
+            ObstacleShape::Box { half_extents } => {
+                let axis = (0..axes.min(3))
+                    .min_by(|&i, &j| {
+                        (half_extents[i] - offset[i].abs()).partial_cmp(&(half_extents[j] - offset[j].abs())).unwrap()
+                    })
+                    .unwrap_or(0);
+                exit[axis] = half_extents[axis].copysign(offset[axis]);
+            },
+
+            // Through the side, or through a cap when it is nearer
+            ObstacleShape::Cylinder { radius, half_height } => {
+                let radial = (offset.x.powi(2) + offset.y.powi(2)).sqrt();
+                if axes >= 3 && half_height - offset.z.abs() < radius - radial {
+                    exit.z = half_height.copysign(offset.z);
+                } else if radial > 0f32 {
+                    exit.x *= radius / radial;
+                    exit.y *= radius / radial;
+                } else {
+                    exit.x = *radius;
+                }
+            }
+
+        }
+
+        center + exit
+
+    }
+
+    /// [position, velocity] state of the obstacle surface point closest to an agent, moving with the
+    /// obstacle, and whether the agent is inside the obstacle
+    ///  obstacle_state: [position, velocity] state of the obstacle center
+    ///  agent_state: [position, velocity] state of the agent
+    pub fn surface_state(&self, obstacle_state: &DVector<f32>, agent_state: &DVector<f32>) -> (DVector<f32>, bool) {
+
+        let n = obstacle_state.len() / 2;
+        let center = position(obstacle_state);
+        let point = position(agent_state);
+
+        // An agent inside the obstacle is given the surface point it can leave through
+        let inside = self.contains(&center, &point);
+        let closest = if inside {
+            self.exit_point(&center, &point, n)
+        } else {
+            self.closest_point(&center, &point)
+        };
+
+        let mut state = obstacle_state.clone();
+        for i in 0..n.min(3) {
+            state[i] = closest[i];
+        }
+
+        (state, inside)
+
+    }
+
+    /// Wireframe of the obstacle as polylines
+    pub fn outline(&self, center: &Vector3<f32>) -> Vec<Vec<Vector3<f32>>> {
+
+        match self {
+
+            // Great circles in the xy, xz and yz planes
+            ObstacleShape::Sphere { radius } => {
+                vec![
+                    circle(center, *radius, |c, s| Vector3::new(c, s, 0f32)),
+                    circle(center, *radius, |c, s| Vector3::new(c, 0f32, s)),
+                    circle(center, *radius, |c, s| Vector3::new(0f32, c, s))
+                ]
+            },
+
+            // 12 edges
+            ObstacleShape::Box { half_extents } => {
+                let corner = |i: usize| -> Vector3<f32> {
+                    let sign = |bit: usize| if i & bit == 0 { -1f32 } else { 1f32 };
+                    center + Vector3::new(sign(1) * half_extents.x, sign(2) * half_extents.y, sign(4) * half_extents.z)
+                };
+                let mut edges = Vec::new();
+                for i in 0..8 {
+                    for bit in [1, 2, 4] {
+                        if i & bit == 0 {
+                            edges.push(vec![corner(i), corner(i | bit)]);
+                        }
+                    }
+                }
+                edges
+            },
+
+            // Top and bottom circles joined by 4 vertical lines
+            ObstacleShape::Cylinder { radius, half_height } => {
+                let bottom = center - Vector3::new(0f32, 0f32, *half_height);
+                let top = center + Vector3::new(0f32, 0f32, *half_height);
+                let mut lines = vec![
+                    circle(&bottom, *radius, |c, s| Vector3::new(c, s, 0f32)),
+                    circle(&top, *radius, |c, s| Vector3::new(c, s, 0f32))
+                ];
+                for (x, y) in [(1f32, 0f32), (0f32, 1f32), (-1f32, 0f32), (0f32, -1f32)] {
+                    let offset = Vector3::new(x * radius, y * radius, 0f32);
+                    lines.push(vec![bottom + offset, top + offset]);
+                }
+                lines
+            }
+
+        }
+
+    }
+
+    /// Closed outline of the obstacle in the xy plane
+    pub fn footprint(&self, center: &Vector3<f32>) -> Vec<Vector3<f32>> {
+
+        match self {
+
+            ObstacleShape::Sphere { radius } | ObstacleShape::Cylinder { radius, .. } => {
+                circle(center, *radius, |c, s| Vector3::new(c, s, 0f32))
+            },
+
+            ObstacleShape::Box { half_extents } => {
+                [(-1f32, -1f32), (1f32, -1f32), (1f32, 1f32), (-1f32, 1f32), (-1f32, -1f32)].iter()
+                    .map(|(x, y)| center + Vector3::new(x * half_extents.x, y * half_extents.y, 0f32))
+                    .collect()
+            }
+
+        }
+
+    }
+
+}
+
+/// Closed circle about a center, in the plane spanned by the (cos, sin) components of a mapping
+fn circle<F: Fn(f32, f32) -> Vector3<f32>>(center: &Vector3<f32>, radius: f32, plane: F) -> Vec<Vector3<f32>> {
+
+    (0..=OUTLINE_RESOLUTION)
+        .map(|i| {
+            let angle = 2f32 * std::f32::consts::PI * i as f32 / OUTLINE_RESOLUTION as f32;
+            center + plane(radius * angle.cos(), radius * angle.sin())
+        })
+        .collect()
+
+}
+
+/// An obstacle placed in a scenario
+#[derive(Clone, Debug)]
+pub struct ObstacleSpec {
+    pub shape: ObstacleShape,
+
+    /// Initial position of the obstacle center
+    pub position: Vector3<f32>,
+
+    /// Motion of the obstacle center from its initial position
+    pub motion: TargetMotion
+}
+
+impl ObstacleSpec {
+
+    pub fn stationary(shape: ObstacleShape, position: Vector3<f32>) -> Self {
+        Self { shape, position, motion: TargetMotion::Stationary }
+    }
+
+}
+
+/// Shape of a named obstacle, written alongside the results for plotting
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ObstacleRecord {
+    pub name: String,
+    pub shape: ObstacleShape
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_obstacle_distances() {
+
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let sphere = ObstacleShape::Sphere { radius: 1.0 };
+        let cube = ObstacleShape::Box { half_extents: Vector3::new(1.0, 1.0, 1.0) };
+        let cylinder = ObstacleShape::Cylinder { radius: 1.0, half_height: 2.0 };
+
+        let point = Vector3::new(3.0, 0.0, 0.0);
+        assert!((sphere.distance(&center, &point) - 2.0).abs() < 1E-6);
+        assert!((cube.distance(&center, &Vector3::new(2.0, 2.0, 0.0)) - 2f32.sqrt()).abs() < 1E-6);
+        assert!((cylinder.distance(&center, &Vector3::new(0.0, 0.0, 5.0)) - 3.0).abs() < 1E-6);
+        assert_eq!(sphere.distance(&center, &Vector3::new(0.5, 0.0, 0.0)), 0.0);
+
+        // Paths through and around the obstacles
+        let start = Vector3::new(-5.0, 0.5, 0.0);
+        let end = Vector3::new(5.0, 0.5, 0.0);
+        assert!(sphere.blocks(&center, &start, &end, 0.0));
+        assert!(cube.blocks(&center, &start, &end, 0.0));
+        assert!(!cylinder.blocks(&center, &Vector3::new(-5.0, 3.0, 0.0), &Vector3::new(5.0, 3.0, 0.0), 1.0));
+        assert!((cylinder.segment_distance(&center, &Vector3::new(-5.0, 3.0, 0.0), &Vector3::new(5.0, 3.0, 0.0)) - 2.0).abs() < 1E-3);
+
+    }
+
+    #[test]
+    fn test_surface_state() {
+
+        let obstacle = DVector::from_vec(vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+        let sphere = ObstacleShape::Sphere { radius: 2.0 };
+        let cube = ObstacleShape::Box { half_extents: Vector3::new(1.0, 2.0, 3.0) };
+        let cylinder = ObstacleShape::Cylinder { radius: 2.0, half_height: 1.0 };
+
+        // Outside, the closest surface point moves with the obstacle
+        let agent = DVector::from_vec(vec![4.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        let (state, inside) = sphere.surface_state(&obstacle, &agent);
+        assert_eq!(state, DVector::from_vec(vec![2.0, 0.0, 0.0, 1.0, 0.0, 0.0]));
+        assert!(!inside);
+
+        // Inside, the surface point the agent can leave through
+        let agent = DVector::from_vec(vec![0.0, 0.5, 0.0, 0.0, 0.0, 0.0]);
+        let (state, inside) = sphere.surface_state(&obstacle, &agent);
+        assert!((state.rows(0, 3) - Vector3::new(0.0, 2.0, 0.0)).norm() < 1E-6);
+        assert!(inside);
+
+        let agent = DVector::from_vec(vec![0.5, 1.8, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(cube.surface_state(&obstacle, &agent).0.rows(0, 3), Vector3::new(0.5, 2.0, 0.0));
+        let agent = DVector::from_vec(vec![0.5, 0.5, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(cylinder.surface_state(&obstacle, &agent).0.rows(0, 3), Vector3::new(0.5, 0.5, 1.0));
+
+        // Planar agents leave within the plane
+        let obstacle = DVector::from_vec(vec![0.0, 0.0, 0.0, 0.0]);
+        let agent = DVector::from_vec(vec![0.5, 0.5, 0.0, 0.0]);
+        let (state, inside) = cylinder.surface_state(&obstacle, &agent);
+        assert!((state - DVector::from_vec(vec![2f32.sqrt(), 2f32.sqrt(), 0.0, 0.0])).norm() < 1E-6);
+        assert!(inside);
+
+        // Centered agents still get a direction out
+        assert_eq!(sphere.surface_state(&obstacle, &obstacle).0, DVector::from_vec(vec![2.0, 0.0, 0.0, 0.0]));
+
+    }
+
+}
@@ -1,15 +1,40 @@
 
+use nalgebra::Vector3;
 use plotters::prelude::*;
 use mads::ecs::resources::{SimulationTimeHistory, SimulationResult};
 
+use crate::obstacles::ObstacleRecord;
+
 // TODO: axes may be swapped somewhere
 
+/// Plots the trajectories in 2D for planar scenarios, with [x, y, vx, vy] states, and 3D otherwise
+/// Obstacles are drawn in black at their final position
+pub fn plot_trajectories(
+    times: &SimulationTimeHistory,
+    data: &SimulationResult,
+    obstacles: &[ObstacleRecord],
+    filepath: &str
+) -> Result<(), Box<dyn std::error::Error>>
+{
+    let planar = data.data.values()
+        .filter_map(|trajectory| trajectory.first())
+        .all(|fullstate| fullstate.data.len() == 4);
+
+    if planar {
+        plot_trajectory_2d(times, data, obstacles, filepath)
+    } else {
+        plot_trajectory_3d(times, data, obstacles, filepath)
+    }
+}
+
 pub fn plot_trajectory_3d(
     times: &SimulationTimeHistory,
-    data: &SimulationResult
+    data: &SimulationResult,
+    obstacles: &[ObstacleRecord],
+    filepath: &str
 ) -> Result<(), Box<dyn std::error::Error>>
 {
-    let root = BitMapBackend::new("trajectory3d.png", (640, 480)).into_drawing_area();
+    let root = BitMapBackend::new(filepath, (640, 480)).into_drawing_area();
     root.fill(&WHITE)?;
 
     let mut chart = ChartBuilder::on(&root)
@@ -30,6 +55,8 @@ pub fn plot_trajectory_3d(
     for (id, trajectory) in data.data.iter() {
 
         let name = &id.name;
+        let obstacle = obstacles.iter().find(|obstacle| obstacle.name == *name);
+        let color = if obstacle.is_some() { &BLACK } else { &RED };
 
         // recover position in XYZ
         let xyz_traj: Vec<(f32, f32, f32)> = trajectory.iter()
@@ -39,11 +66,18 @@ pub fn plot_trajectory_3d(
             })
             .collect();
 
+        // Obstacle wireframe at its final position
+        if let (Some(obstacle), Some(&(x, y, z))) = (obstacle, xyz_traj.last()) {
+            for line in obstacle.shape.outline(&Vector3::new(x, y, z)) {
+                chart.draw_series(LineSeries::new(line.iter().map(|p| (p.x, p.y, p.z)), &BLACK))?;
+            }
+        }
+
         // Draw
         chart
-            .draw_series(LineSeries::new(xyz_traj, &RED))?
+            .draw_series(LineSeries::new(xyz_traj, color))?
             .label(name)
-            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], &RED));
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
 
     }
 
@@ -58,6 +92,92 @@ pub fn plot_trajectory_3d(
 
 
 
+/// Plots the x-y trajectories of planar scenarios
+pub fn plot_trajectory_2d(
+    _times: &SimulationTimeHistory,
+    data: &SimulationResult,
+    obstacles: &[ObstacleRecord],
+    filepath: &str
+) -> Result<(), Box<dyn std::error::Error>>
+{
+    let root = BitMapBackend::new(filepath, (640, 480)).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    // recover position in XY
+    let xy_trajectories: Vec<(&String, Vec<(f32, f32)>)> = data.data.iter()
+        .map(|(id, trajectory)| {
+            let xy_traj = trajectory.iter()
+                .map(|fullstate| (fullstate.data[0], fullstate.data[1]))
+                .collect();
+            (&id.name, xy_traj)
+        })
+        .collect();
+
+    // Fit the axes to the trajectories with a margin
+    let (mut x_min, mut x_max, mut y_min, mut y_max) = (f32::MAX, f32::MIN, f32::MAX, f32::MIN);
+    for (_name, xy_traj) in xy_trajectories.iter() {
+        for (x, y) in xy_traj.iter() {
+            x_min = x_min.min(*x);
+            x_max = x_max.max(*x);
+            y_min = y_min.min(*y);
+            y_max = y_max.max(*y);
+        }
+    }
+    for obstacle in obstacles.iter() {
+        let last = xy_trajectories.iter()
+            .find(|(name, _xy_traj)| **name == obstacle.name)
+            .and_then(|(_name, xy_traj)| xy_traj.last());
+        if let Some(&(x, y)) = last {
+            for p in obstacle.shape.footprint(&Vector3::new(x, y, 0f32)) {
+                x_min = x_min.min(p.x);
+                x_max = x_max.max(p.x);
+                y_min = y_min.min(p.y);
+                y_max = y_max.max(p.y);
+            }
+        }
+    }
+    if x_min > x_max {
+        (x_min, x_max, y_min, y_max) = (-10f32, 10f32, -10f32, 10f32);
+    }
+    let margin = 0.05 * (x_max - x_min).max(y_max - y_min).max(1f32);
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption("Trajectory", ("sans-serif", 50).into_font())
+        .margin(5)
+        .x_label_area_size(30)
+        .y_label_area_size(30)
+        .build_cartesian_2d((x_min - margin)..(x_max + margin), (y_min - margin)..(y_max + margin))?;
+
+    chart.configure_mesh().draw()?;
+
+    for (name, xy_traj) in xy_trajectories {
+
+        let obstacle = obstacles.iter().find(|obstacle| obstacle.name == *name);
+        let color = if obstacle.is_some() { &BLACK } else { &RED };
+
+        // Obstacle footprint at its final position
+        if let (Some(obstacle), Some(&(x, y))) = (obstacle, xy_traj.last()) {
+            let footprint = obstacle.shape.footprint(&Vector3::new(x, y, 0f32));
+            chart.draw_series(LineSeries::new(footprint.iter().map(|p| (p.x, p.y)), &BLACK))?;
+        }
+
+        // Draw
+        chart
+            .draw_series(LineSeries::new(xy_traj, color))?
+            .label(name)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+
+    }
+
+    chart
+        .configure_series_labels()
+        .background_style(&WHITE.mix(0.8))
+        .border_style(&BLACK)
+        .draw()?;
+
+    Ok(())
+}
+
 pub fn plot_trajectory(
     times: &SimulationTimeHistory,
     data: &SimulationResult
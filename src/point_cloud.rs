@@ -0,0 +1,462 @@
+
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+use serde::{Serialize, Deserialize};
+
+/// Errors raised while loading a formation from a file
+#[derive(Error, Debug)]
+pub enum PointCloudError {
+
+    #[error("unable to read point cloud file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("unable to parse point cloud: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("unable to parse point cloud: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("unable to read image: {0}")]
+    Image(#[from] image::ImageError),
+
+    #[error("unable to parse point cloud line {line}: {message}")]
+    Parse { line: usize, message: String },
+
+    #[error("unsupported point cloud format: {0}")]
+    UnsupportedFormat(String),
+
+    #[error("point cloud contains no points")]
+    Empty
+
+}
+
+/// Rescaling applied to a point cloud after loading
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Normalization {
+
+    /// Keep the original coordinates
+    None,
+
+    /// Move the centroid to the origin
+    Center,
+
+    /// Move the centroid to the origin and fit the points inside the unit sphere
+    UnitSphere,
+
+    /// Move the bounding box center to the origin and fit the points inside the cube [-1, 1]^3
+    UnitCube
+
+}
+
+/// Formation given by a set of points loaded from a file
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PointCloud {
+    pub points: Vec<(f32, f32, f32)>,
+    pub normalization: Normalization
+}
+
+impl PointCloud {
+
+    pub fn new(points: Vec<(f32, f32, f32)>, normalization: Normalization) -> Self {
+        Self { points: normalize(&points, normalization), normalization }
+    }
+
+    /// Loads a point list or mesh vertices (.csv, .json, .ply, .obj)
+    pub fn from_file<P: AsRef<Path>>(path: P, normalization: Normalization) -> Result<Self, PointCloudError> {
+
+        let extension = path.as_ref().extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase())
+            .unwrap_or_default();
+
+        let contents = fs::read_to_string(&path)?;
+        let points = match extension.as_str() {
+            "csv" => parse_csv(&contents)?,
+            "json" => parse_json(&contents)?,
+            "ply" => parse_ply(&contents)?,
+            "obj" => parse_obj(&contents)?,
+            _ => return Err(PointCloudError::UnsupportedFormat(extension))
+        };
+
+        if points.is_empty() {
+            return Err(PointCloudError::Empty);
+        }
+
+        Ok(Self::new(points, normalization))
+
+    }
+
+    /// Loads the pixels of an image as points in the z = 0 plane, e.g. a logo for a drone show
+    ///  threshold: luminance (0-255) below which a pixel belongs to the shape
+    ///  invert: select pixels above the threshold instead, e.g. a light shape on a dark background
+    pub fn from_image<P: AsRef<Path>>(path: P, threshold: u8, invert: bool, normalization: Normalization)
+        -> Result<Self, PointCloudError>
+    {
+
+        let image = image::open(path)?.to_luma8();
+
+        // Image rows grow downwards
+        let points: Vec<(f32, f32, f32)> = image.enumerate_pixels()
+            .filter(|(_x, _y, pixel)| (pixel.0[0] < threshold) != invert)
+            .map(|(x, y, _pixel)| (x as f32, -(y as f32), 0f32))
+            .collect();
+
+        if points.is_empty() {
+            return Err(PointCloudError::Empty);
+        }
+
+        Ok(Self::new(points, normalization))
+
+    }
+
+    /// Resamples the point cloud to exactly num_samples points. Normalized clouds are multiplied by
+    /// scale, clouds without normalization keep their original coordinates
+    pub fn sample(&self, scale: f32, num_samples: u32) -> Vec<(f32, f32, f32)> {
+
+        let scale = match self.normalization {
+            Normalization::None | Normalization::Center => 1f32,
+            Normalization::UnitSphere | Normalization::UnitCube => scale
+        };
+
+        resample(&self.points, num_samples as usize).iter()
+            .map(|(x, y, z)| (scale * x, scale * y, scale * z))
+            .collect()
+
+    }
+
+}
+
+/// Parses rows of x,y[,z] coordinates. A header row is skipped
+pub fn parse_csv(contents: &str) -> Result<Vec<(f32, f32, f32)>, PointCloudError> {
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(contents.as_bytes());
+
+    let mut points = Vec::new();
+    for (line, record) in reader.records().enumerate() {
+
+        let record = record?;
+        let values: Result<Vec<f32>, _> = record.iter().map(|field| field.parse::<f32>()).collect();
+
+        match values {
+            Ok(values) => points.push(to_point(&values, line + 1)?),
+            Err(_) if line == 0 => continue, // header
+            Err(error) => return Err(PointCloudError::Parse { line: line + 1, message: error.to_string() })
+        }
+
+    }
+
+    Ok(points)
+
+}
+
+/// Parses a JSON array of [x, y(, z)] arrays or {"x", "y"(, "z")} objects
+pub fn parse_json(contents: &str) -> Result<Vec<(f32, f32, f32)>, PointCloudError> {
+
+    let value: serde_json::Value = serde_json::from_str(contents)?;
+    let entries = match value.as_array() {
+        Some(entries) => entries,
+        None => return Err(PointCloudError::Parse { line: 1, message: "expected an array of points".to_string() })
+    };
+
+    let mut points = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+
+        let values: Option<Vec<f32>> = match entry {
+            serde_json::Value::Array(coordinates) => {
+                coordinates.iter().map(|c| c.as_f64().map(|c| c as f32)).collect()
+            },
+            serde_json::Value::Object(coordinates) => {
+                ["x", "y", "z"].iter()
+                    .filter_map(|axis| coordinates.get(*axis))
+                    .map(|c| c.as_f64().map(|c| c as f32))
+                    .collect()
+            },
+            _ => None
+        };
+
+        match values {
+            Some(values) => points.push(to_point(&values, i + 1)?),
+            None => return Err(PointCloudError::Parse { line: i + 1, message: "expected numeric coordinates".to_string() })
+        }
+
+    }
+
+    Ok(points)
+
+}
+
+/// Parses the vertices of an ASCII PLY file
+pub fn parse_ply(contents: &str) -> Result<Vec<(f32, f32, f32)>, PointCloudError> {
+
+    let mut lines = contents.lines().enumerate();
+    let mut num_vertices = 0usize;
+
+    // Header
+    for (i, line) in lines.by_ref() {
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", format, ..] if *format != "ascii" => {
+                return Err(PointCloudError::UnsupportedFormat(format!("ply {}", format)));
+            },
+            ["element", "vertex", count] => {
+                num_vertices = count.parse::<usize>()
+                    .map_err(|error| PointCloudError::Parse { line: i + 1, message: error.to_string() })?;
+            },
+            ["end_header"] => break,
+            _ => continue
+        }
+
+    }
+
+    // Vertices come first in the body, the position is given by the first three properties
+    let mut points = Vec::with_capacity(num_vertices);
+    for (i, line) in lines.take(num_vertices) {
+        points.push(parse_coordinates(line.split_whitespace(), i + 1)?);
+    }
+
+    Ok(points)
+
+}
+
+/// Parses the vertices of an OBJ file
+pub fn parse_obj(contents: &str) -> Result<Vec<(f32, f32, f32)>, PointCloudError> {
+
+    let mut points = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+
+        let mut words = line.split_whitespace();
+        if words.next() == Some("v") {
+            points.push(parse_coordinates(words, i + 1)?);
+        }
+
+    }
+
+    Ok(points)
+
+}
+
+/// Reads the x, y(, z) coordinates at the start of a line
+fn parse_coordinates<'a, I: Iterator<Item = &'a str>>(words: I, line: usize) -> Result<(f32, f32, f32), PointCloudError> {
+
+    let values: Result<Vec<f32>, _> = words.take(3).map(|word| word.parse::<f32>()).collect();
+    match values {
+        Ok(values) => to_point(&values, line),
+        Err(error) => Err(PointCloudError::Parse { line, message: error.to_string() })
+    }
+
+}
+
+/// Point from 2 or 3 coordinates, 2D points lie in the z = 0 plane
+fn to_point(values: &[f32], line: usize) -> Result<(f32, f32, f32), PointCloudError> {
+
+    match values {
+        [x, y] => Ok((*x, *y, 0f32)),
+        [x, y, z, ..] => Ok((*x, *y, *z)),
+        _ => Err(PointCloudError::Parse { line, message: "expected at least 2 coordinates".to_string() })
+    }
+
+}
+
+/// Translates and scales points according to a Normalization
+pub fn normalize(points: &[(f32, f32, f32)], normalization: Normalization) -> Vec<(f32, f32, f32)> {
+
+    if points.is_empty() {
+        return Vec::new();
+    }
+
+    let n = points.len() as f32;
+    let centroid = points.iter()
+        .fold((0f32, 0f32, 0f32), |c, p| (c.0 + p.0 / n, c.1 + p.1 / n, c.2 + p.2 / n));
+
+    let min = points.iter().fold((f32::MAX, f32::MAX, f32::MAX), |m, p| (m.0.min(p.0), m.1.min(p.1), m.2.min(p.2)));
+    let max = points.iter().fold((f32::MIN, f32::MIN, f32::MIN), |m, p| (m.0.max(p.0), m.1.max(p.1), m.2.max(p.2)));
+
+    let (center, extent) = match normalization {
+
+        Normalization::None => return points.to_vec(),
+
+        Normalization::Center => (centroid, 1f32),
+
+        Normalization::UnitSphere => {
+            let extent = points.iter()
+                .map(|p| ((p.0 - centroid.0).powi(2) + (p.1 - centroid.1).powi(2) + (p.2 - centroid.2).powi(2)).sqrt())
+                .fold(0f32, f32::max);
+            (centroid, extent)
+        },
+
+        Normalization::UnitCube => {
+            let center = ((min.0 + max.0) / 2f32, (min.1 + max.1) / 2f32, (min.2 + max.2) / 2f32);
+            let extent = ((max.0 - min.0).max(max.1 - min.1).max(max.2 - min.2)) / 2f32;
+            (center, extent)
+        }
+
+    };
+
+    // Single points or coincident clouds are only translated
+    let scale = if extent > 0f32 { 1f32 / extent } else { 1f32 };
+
+    points.iter()
+        .map(|p| ((p.0 - center.0) * scale, (p.1 - center.1) * scale, (p.2 - center.2) * scale))
+        .collect()
+
+}
+
+/// Resamples a point cloud to exactly num_samples points
+///
+/// Larger clouds are reduced by farthest point sampling, which keeps the outline of the shape.
+/// Smaller clouds are grown by inserting midpoints between the points furthest from their
+/// nearest neighbor.
+pub fn resample(points: &[(f32, f32, f32)], num_samples: usize) -> Vec<(f32, f32, f32)> {
+
+    if points.is_empty() || num_samples == points.len() {
+        return points.iter().cloned().take(num_samples).collect();
+    }
+
+    if num_samples < points.len() {
+        farthest_point_sampling(points, num_samples)
+    } else {
+        densify(points, num_samples)
+    }
+
+}
+
+fn distance_squared(p1: &(f32, f32, f32), p2: &(f32, f32, f32)) -> f32 {
+    (p1.0 - p2.0).powi(2) + (p1.1 - p2.1).powi(2) + (p1.2 - p2.2).powi(2)
+}
+
+/// Greedily selects the point furthest from those already selected, starting from the first
+fn farthest_point_sampling(points: &[(f32, f32, f32)], num_samples: usize) -> Vec<(f32, f32, f32)> {
+
+    let mut selected = Vec::with_capacity(num_samples);
+    let mut distances = vec![f32::MAX; points.len()];
+    let mut next = 0usize;
+
+    while selected.len() < num_samples {
+
+        let point = points[next];
+        selected.push(point);
+
+        for (i, other) in points.iter().enumerate() {
+            distances[i] = distances[i].min(distance_squared(&point, other));
+        }
+
+        next = distances.iter()
+            .enumerate()
+            .fold((0usize, f32::MIN), |best, (i, d)| if *d > best.1 { (i, *d) } else { best })
+            .0;
+
+    }
+
+    selected
+
+}
+
+/// Inserts midpoints between points and their nearest neighbors until num_samples points exist
+fn densify(points: &[(f32, f32, f32)], num_samples: usize) -> Vec<(f32, f32, f32)> {
+
+    let mut result = points.to_vec();
+
+    while result.len() < num_samples {
+
+        // Nearest neighbor pairs, largest gaps first
+        let mut pairs: Vec<(usize, usize, f32)> = Vec::new();
+        for (i, p1) in result.iter().enumerate() {
+            let nearest = result.iter().enumerate()
+                .filter(|(j, _p2)| *j != i)
+                .map(|(j, p2)| (j, distance_squared(p1, p2)))
+                .fold(None, |best: Option<(usize, f32)>, (j, d)| match best {
+                    Some((_, best_d)) if best_d <= d => best,
+                    _ => Some((j, d))
+                });
+            if let Some((j, d)) = nearest {
+                let pair = (i.min(j), i.max(j), d);
+                if !pairs.iter().any(|(a, b, _)| (*a, *b) == (pair.0, pair.1)) {
+                    pairs.push(pair);
+                }
+            }
+        }
+        pairs.sort_by(|p1, p2| p2.2.partial_cmp(&p1.2).unwrap_or(std::cmp::Ordering::Equal));
+
+        // A single point has no neighbor to split towards
+        if pairs.is_empty() {
+            let point = result[0];
+            result.resize(num_samples, point);
+            break;
+        }
+
+        let needed = num_samples - result.len();
+        let midpoints: Vec<(f32, f32, f32)> = pairs.iter()
+            .take(needed)
+            .map(|(i, j, _d)| {
+                let (p1, p2) = (result[*i], result[*j]);
+                ((p1.0 + p2.0) / 2f32, (p1.1 + p2.1) / 2f32, (p1.2 + p2.2) / 2f32)
+            })
+            .collect();
+        result.extend(midpoints);
+
+    }
+
+    result
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_formats() {
+
+        let csv = "x, y, z\n0, 0, 0\n1, 2, 3\n4, 5\n";
+        assert_eq!(parse_csv(csv).unwrap(), vec![(0.0, 0.0, 0.0), (1.0, 2.0, 3.0), (4.0, 5.0, 0.0)]);
+
+        let json = r#"[[0, 0, 0], [1, 2], {"x": 3, "y": 4, "z": 5}]"#;
+        assert_eq!(parse_json(json).unwrap(), vec![(0.0, 0.0, 0.0), (1.0, 2.0, 0.0), (3.0, 4.0, 5.0)]);
+
+        let ply = "ply\nformat ascii 1.0\nelement vertex 2\nproperty float x\nproperty float y\n\
+                   property float z\nelement face 1\nend_header\n0 0 0\n1 2 3\n3 0 1 2\n";
+        assert_eq!(parse_ply(ply).unwrap(), vec![(0.0, 0.0, 0.0), (1.0, 2.0, 3.0)]);
+
+        let obj = "# comment\nv 0 0 0\nvn 0 0 1\nv 1 2 3\nf 1 2 1\n";
+        assert_eq!(parse_obj(obj).unwrap(), vec![(0.0, 0.0, 0.0), (1.0, 2.0, 3.0)]);
+
+        assert!(parse_csv("0, 0\n1, a\n").is_err());
+
+    }
+
+    #[test]
+    fn test_normalize() {
+
+        let points = vec![(0.0, 0.0, 0.0), (4.0, 0.0, 0.0), (2.0, 2.0, 0.0)];
+        let result = normalize(&points, Normalization::UnitCube);
+        assert_eq!(result, vec![(-1.0, -0.5, 0.0), (1.0, -0.5, 0.0), (0.0, 0.5, 0.0)]);
+
+        let result = normalize(&points, Normalization::UnitSphere);
+        let max = result.iter().map(|p| (p.0 * p.0 + p.1 * p.1 + p.2 * p.2).sqrt()).fold(0f32, f32::max);
+        assert!((max - 1.0).abs() < 1E-6);
+
+    }
+
+    #[test]
+    fn test_resample() {
+
+        let points: Vec<(f32, f32, f32)> = (0..100).map(|i| (i as f32, 0.0, 0.0)).collect();
+
+        let result = resample(&points, 3);
+        assert_eq!(result, vec![(0.0, 0.0, 0.0), (99.0, 0.0, 0.0), (49.0, 0.0, 0.0)]);
+
+        let result = resample(&points[0..2], 5);
+        assert_eq!(result.len(), 5);
+        assert!(result.contains(&(0.5, 0.0, 0.0)));
+
+        assert_eq!(resample(&points[0..1], 3), vec![(0.0, 0.0, 0.0); 3]);
+
+    }
+
+}
@@ -4,34 +4,62 @@ use mads::simulator::Simulator;
 use mads::ecs::resources::*;
 use mads::log::{LogDataType, Logger};
 
-use crate::plot::plot_trajectory_3d;
-use crate::logger::FormFlightLogger;
+use crate::plot::plot_trajectories;
+use crate::logger::{FormFlightLogger, obstacle_records};
+use crate::safety::SafetyMonitor;
+use crate::config::OutputConfig;
 
-pub fn post_process<T: Scenario>(simulator: &Simulator<T>) {
+pub fn post_process<T: Scenario>(simulator: &Simulator<T>, output: &OutputConfig, safety: &SafetyMonitor) {
 
     // TODO: safely unwrap resources.get()
     let time_history = simulator.get_state().ecs.resources.get::<SimulationTimeHistory>().unwrap();
     let result = simulator.get_state().ecs.resources.get::<SimulationResult>().unwrap();
 
     let logger = FormFlightLogger;
-    if let Err(err) = logger.to_csv(&simulator.get_state(), "./results.csv", LogDataType::SimResult) {
+    if let Err(err) = logger.to_csv(&simulator.get_state(), &output.path(&output.results), LogDataType::SimResult) {
         println!("csv write error, {}", err);
     };
 
-    if let Err(err) = logger.assignments_to_json(&simulator.get_state(), "./assignments.json") {
+    if let Err(err) = logger.assignments_to_json(&simulator.get_state(), &output.path(&output.assignments)) {
         println!("json write error, {}", err);
     };
 
-    if let Err(err) = logger.sim_id_to_json(&simulator.get_state(), "./entities.json") {
+    if let Err(err) = logger.sim_id_to_json(&simulator.get_state(), &output.path(&output.entities)) {
         println!("json write error, {}", err);
     };
 
-    // (optional)
-    match plot_trajectory_3d(&time_history, &result) {
+    if let Err(err) = logger.obstacles_to_json(&simulator.get_state(), &output.path(&output.obstacles)) {
+        println!("json write error, {}", err);
+    };
+
+    let obstacles = obstacle_records(&simulator.get_state());
+
+    if let Err(err) = safety.samples_to_csv(&output.path(&output.safety)) {
+        println!("csv write error, {}", err);
+    };
 
-        Ok(()) => println!("plot done"),
-        Err(_) => println!("plot error")
+    if let Err(err) = logger.safety_to_json(safety, &output.path(&output.safety_summary)) {
+        println!("json write error, {}", err);
+    };
 
+    if let Err(err) = logger.actuators_to_csv(&simulator.get_state(), &output.path(&output.actuators)) {
+        println!("csv write error, {}", err);
     };
 
+    if let Err(err) = logger.mission_to_json(&simulator.get_state(), &output.path(&output.mission)) {
+        println!("json write error, {}", err);
+    };
+
+    // (optional)
+    if let Some(plot) = &output.plot {
+
+        match plot_trajectories(&time_history, &result, &obstacles, &output.path(plot)) {
+
+            Ok(()) => println!("plot done"),
+            Err(_) => println!("plot error")
+
+        };
+
+    }
+
 }
@@ -0,0 +1,295 @@
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use nalgebra::Vector3;
+use serde::{Serialize, Deserialize};
+
+// MADS
+use mads::ecs::resources::{SimulationTimeHistory, SimulationResult};
+
+// formflight
+use crate::obstacles::{ObstacleShape, ObstacleRecord};
+use crate::tracking_scenario::position;
+
+/// Distances below which a run is considered unsafe
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SafetyConfig {
+
+    /// Minimum distance between agents
+    pub separation: f32,
+
+    /// Minimum distance between agents and obstacle surfaces
+    pub obstacle_clearance: f32
+
+}
+
+impl Default for SafetyConfig {
+
+    fn default() -> Self {
+        Self { separation: 1f32, obstacle_clearance: 0.5 }
+    }
+
+}
+
+/// Safety of a single time step
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct SafetySample {
+    pub time: f32,
+
+    /// Smallest distance between two agents, None with fewer than two agents
+    pub min_agent_distance: Option<f32>,
+
+    /// Agent pairs closer than the separation
+    pub agent_violations: u32,
+
+    /// Smallest distance between an agent and an obstacle, None without obstacles
+    pub min_obstacle_clearance: Option<f32>,
+
+    /// Agent-obstacle pairs closer than the obstacle clearance
+    pub obstacle_violations: u32
+}
+
+/// Collision and near-miss metrics accumulated over a run
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SafetyMonitor {
+
+    pub config: SafetyConfig,
+
+    #[serde(skip)]
+    pub samples: Vec<SafetySample>,
+
+    /// Smallest agent-agent distance over the run
+    pub min_agent_distance: Option<f32>,
+
+    /// Smallest agent-obstacle distance over the run
+    pub min_obstacle_clearance: Option<f32>,
+
+    /// Number of times an agent pair came closer than the separation
+    pub agent_violations: u32,
+
+    /// Number of times an agent came closer to an obstacle than the obstacle clearance
+    pub obstacle_violations: u32,
+
+    /// Time of the first agent-agent violation
+    pub first_agent_violation: Option<f32>,
+
+    /// Time of the first agent-obstacle violation
+    pub first_obstacle_violation: Option<f32>,
+
+    /// Time of the first violation of each agent involved in one, by name
+    pub first_violations: BTreeMap<String, f32>,
+
+    // Pairs in violation at the previous sample, a violation is counted when a pair enters it
+    #[serde(skip)]
+    active_agent_pairs: HashSet<(String, String)>,
+
+    #[serde(skip)]
+    active_obstacle_pairs: HashSet<(String, String)>
+
+}
+
+impl SafetyMonitor {
+
+    pub fn new(config: SafetyConfig) -> Self {
+        Self { config, ..Self::default() }
+    }
+
+    /// Computes the metrics from stored trajectories. Samples are matched to the time history
+    /// by index, samples beyond the end of the time history are ignored
+    ///  agents: names of the Agent entities
+    ///  obstacles: names and shapes of the Obstacle entities
+    pub fn from_results(
+        config: SafetyConfig,
+        times: &SimulationTimeHistory,
+        result: &SimulationResult,
+        agents: &[String],
+        obstacles: &[ObstacleRecord]
+    ) -> Self
+    {
+
+        let mut monitor = Self::new(config);
+
+        let trajectories: HashMap<&String, Vec<Vector3<f32>>> = result.data.iter()
+            .map(|(id, trajectory)| (&id.name, trajectory.iter().map(|state| position(&state.data)).collect()))
+            .collect();
+
+        let num_samples = trajectories.values().map(|trajectory| trajectory.len()).max().unwrap_or(0);
+        for (k, time) in times.data.iter().take(num_samples).enumerate() {
+
+            let agent_positions: Vec<(&str, Vector3<f32>)> = agents.iter()
+                .filter_map(|name| trajectories.get(name).and_then(|t| t.get(k)).map(|p| (name.as_str(), *p)))
+                .collect();
+
+            let obstacle_positions: Vec<(&str, &ObstacleShape, Vector3<f32>)> = obstacles.iter()
+                .filter_map(|obstacle| {
+                    trajectories.get(&obstacle.name)
+                        .and_then(|t| t.get(k))
+                        .map(|p| (obstacle.name.as_str(), &obstacle.shape, *p))
+                })
+                .collect();
+
+            monitor.record(*time, &agent_positions, &obstacle_positions);
+
+        }
+
+        monitor
+
+    }
+
+    /// Records the agent and obstacle positions at a given time
+    ///  agents: name and position of each Agent
+    ///  obstacles: name, shape and center position of each Obstacle
+    pub fn record(&mut self, time: f32, agents: &[(&str, Vector3<f32>)], obstacles: &[(&str, &ObstacleShape, Vector3<f32>)]) {
+
+        let mut sample = SafetySample { time, ..SafetySample::default() };
+
+        // Agent-agent distances
+        let mut agent_pairs = HashSet::new();
+        for (i, (name_i, p_i)) in agents.iter().enumerate() {
+            for (name_j, p_j) in agents.iter().skip(i + 1) {
+
+                let distance = (p_i - p_j).norm();
+                sample.min_agent_distance = Some(sample.min_agent_distance.map_or(distance, |d| d.min(distance)));
+
+                if distance < self.config.separation {
+                    sample.agent_violations += 1;
+                    agent_pairs.insert((name_i.to_string(), name_j.to_string()));
+                }
+
+            }
+        }
+
+        // Agent-obstacle clearances
+        let mut obstacle_pairs = HashSet::new();
+        for (agent, p) in agents.iter() {
+            for (obstacle, shape, center) in obstacles.iter() {
+
+                let clearance = shape.distance(center, p);
+                sample.min_obstacle_clearance = Some(sample.min_obstacle_clearance.map_or(clearance, |d| d.min(clearance)));
+
+                if clearance < self.config.obstacle_clearance {
+                    sample.obstacle_violations += 1;
+                    obstacle_pairs.insert((agent.to_string(), obstacle.to_string()));
+                }
+
+            }
+        }
+
+        // Violations starting at this sample
+        for (name_i, name_j) in agent_pairs.difference(&self.active_agent_pairs) {
+            self.agent_violations += 1;
+            self.first_agent_violation.get_or_insert(time);
+            self.first_violations.entry(name_i.clone()).or_insert(time);
+            self.first_violations.entry(name_j.clone()).or_insert(time);
+        }
+        for (agent, _obstacle) in obstacle_pairs.difference(&self.active_obstacle_pairs) {
+            self.obstacle_violations += 1;
+            self.first_obstacle_violation.get_or_insert(time);
+            self.first_violations.entry(agent.clone()).or_insert(time);
+        }
+        self.active_agent_pairs = agent_pairs;
+        self.active_obstacle_pairs = obstacle_pairs;
+
+        // Run extrema
+        if let Some(distance) = sample.min_agent_distance {
+            self.min_agent_distance = Some(self.min_agent_distance.map_or(distance, |d| d.min(distance)));
+        }
+        if let Some(clearance) = sample.min_obstacle_clearance {
+            self.min_obstacle_clearance = Some(self.min_obstacle_clearance.map_or(clearance, |d| d.min(clearance)));
+        }
+
+        self.samples.push(sample);
+
+    }
+
+    /// Whether no separation or clearance was violated
+    pub fn is_safe(&self) -> bool {
+        self.agent_violations == 0 && self.obstacle_violations == 0
+    }
+
+    /// Writes the per time step metrics to CSV
+    pub fn samples_to_csv(&self, filepath: &str) -> Result<(), Box<dyn Error>> {
+
+        let mut writer = csv::Writer::from_path(filepath)?;
+        for sample in self.samples.iter() {
+            writer.serialize(sample)?;
+        }
+        writer.flush()?;
+
+        Ok(())
+
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_safety_monitor() {
+
+        let mut monitor = SafetyMonitor::new(SafetyConfig { separation: 1.0, obstacle_clearance: 0.5 });
+        let sphere = ObstacleShape::Sphere { radius: 1.0 };
+        let center = Vector3::new(0.0, 5.0, 0.0);
+
+        // Agents approach each other, stay too close for two samples, then separate
+        for (k, gap) in [3.0f32, 0.5, 0.8, 2.0, 0.5].iter().enumerate() {
+            let agents = [("Agent0", Vector3::new(0.0, 0.0, 0.0)), ("Agent1", Vector3::new(*gap, 0.0, 0.0))];
+            monitor.record(k as f32, &agents, &[("Obstacle0", &sphere, center)]);
+        }
+
+        assert_eq!(monitor.samples.len(), 5);
+        assert_eq!(monitor.min_agent_distance, Some(0.5));
+        assert_eq!(monitor.agent_violations, 2);
+        assert_eq!(monitor.first_agent_violation, Some(1.0));
+        assert_eq!(monitor.first_violations.get("Agent1"), Some(&1.0));
+        assert_eq!(monitor.min_obstacle_clearance, Some(4.0));
+        assert_eq!(monitor.obstacle_violations, 0);
+        assert!(!monitor.is_safe());
+
+    }
+
+    #[test]
+    fn test_safety_from_results() {
+
+        use uuid::Uuid;
+        use nalgebra::DVector;
+        use mads::ecs::components::{SimID, FullState};
+
+        let trajectory = |positions: &[[f32; 3]]| -> Vec<FullState> {
+            positions.iter()
+                .map(|p| FullState { data: DVector::from_vec(vec![p[0], p[1], p[2], 0.0, 0.0, 0.0]) })
+                .collect()
+        };
+
+        // The agents come too close at 0.5 s and the obstacle too close to Agent0 at 1 s. The last
+        // samples are past the end of the time history and are ignored
+        let mut result = SimulationResult { data: HashMap::new() };
+        result.data.insert(SimID { uuid: Uuid::new_v4(), name: "Agent0".to_string() },
+            trajectory(&[[0.0, 0.0, 0.0], [0.0, 0.0, 0.0], [0.0, 0.0, 0.0], [0.0, 0.0, 0.0]]));
+        result.data.insert(SimID { uuid: Uuid::new_v4(), name: "Agent1".to_string() },
+            trajectory(&[[3.0, 0.0, 0.0], [0.5, 0.0, 0.0], [2.0, 0.0, 0.0], [0.1, 0.0, 0.0]]));
+        result.data.insert(SimID { uuid: Uuid::new_v4(), name: "Obstacle0".to_string() },
+            trajectory(&[[0.0, 5.0, 0.0], [0.0, 3.0, 0.0], [0.0, 1.3, 0.0], [0.0, 1.0, 0.0]]));
+        let times = SimulationTimeHistory { data: vec![0.0, 0.5, 1.0] };
+
+        let agents = vec!["Agent0".to_string(), "Agent1".to_string()];
+        let obstacles = vec![ObstacleRecord { name: "Obstacle0".to_string(), shape: ObstacleShape::Sphere { radius: 1.0 } }];
+        let config = SafetyConfig { separation: 1.0, obstacle_clearance: 0.5 };
+        let monitor = SafetyMonitor::from_results(config, &times, &result, &agents, &obstacles);
+
+        assert_eq!(monitor.samples.len(), 3);
+        assert_eq!(monitor.min_agent_distance, Some(0.5));
+        assert_eq!(monitor.agent_violations, 1);
+        assert_eq!(monitor.first_agent_violation, Some(0.5));
+        assert!((monitor.min_obstacle_clearance.unwrap() - 0.3).abs() < 1E-6);
+        assert_eq!(monitor.obstacle_violations, 1);
+        assert_eq!(monitor.first_obstacle_violation, Some(1.0));
+        assert_eq!(monitor.first_violations.get("Agent0"), Some(&0.5));
+        assert_eq!(monitor.first_violations.get("Agent1"), Some(&0.5));
+
+    }
+
+}
@@ -1,9 +1,14 @@
 #![allow(non_snake_case)]
 
 use std::collections::HashMap;
-use nalgebra::{DMatrix, DVector};
+use nalgebra::{DMatrix, DVector, Vector3};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand_distr::StandardNormal;
 use legion::*;
+use legion::storage::Component;
 use uuid::Uuid;
+use serde::{Serialize, Deserialize};
 
 // MADS
 use mads::scene::scenario::Scenario;
@@ -12,33 +17,189 @@ use mads::ecs::components::*;
 use mads::ecs::resources::*;
 
 // formflight
-use crate::ecs::components::{Agent, Target};
-use crate::ecs::resources::{NumAgents, NumTargets, Assignment, AssignmentHistory};
+use crate::ecs::components::{Agent, Target, Obstacle, Capacity, Demand, TargetTrajectory, ObstacleTrajectory};
+use crate::ecs::resources::{NumAgents, NumTargets, NumObstacles, Assignment, AssignmentHistory, AgentStates, ObstacleStates, CommunicationGraph};
+use crate::ecs::resources::{ControlMode, AgentCostMetrics, SolverConvergence, ObstacleAvoidance};
 use crate::ecs::systems::simulate::integrate_lqr_error_dynamics_system;
+use crate::ecs::systems::targets::propagate_targets_system;
+use crate::ecs::systems::obstacles::propagate_obstacles_system;
 use crate::distributions::*;
-use crate::assignments::ot_assignment;
+use crate::avoidance::CollisionAvoidance;
+use crate::obstacles::{ObstacleSpec, ObstacleShape};
+use crate::safety::{SafetyConfig, SafetyMonitor};
+use crate::actuators::{ActuatorLimits, ActuatorState};
+use crate::communication::{Topology, Node, build_edges, to_adjacency_matrix};
+use crate::motion::TargetMotion;
+use crate::formation::{FormationPose, FormationAnimation};
+use crate::mission::{MissionPlan, MissionLog};
+use crate::assignments::{AssignmentStrategy, AssignmentProblem, AssignmentCost, CostMetric, OTAssignment, SwitchingPolicy};
+use crate::assignments::{AssignmentSchedule, AssignmentScheduler};
+
+/// Spatial dimension of the Agent and Target states
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub enum Dimension {
+
+    /// [x, y, vx, vy] states in the plane, e.g. ground robots
+    Planar,
+
+    /// [x, y, z, vx, vy, vz] states
+    #[default]
+    Spatial
+
+}
+
+impl Dimension {
+
+    /// Number of position components of a state
+    pub fn positions(&self) -> usize {
+        match self {
+            Dimension::Planar => 2,
+            Dimension::Spatial => 3
+        }
+    }
+
+    /// [position, velocity] state at rest at a formation point. Planar states drop the z component
+    pub fn state_at_rest(&self, point: &Vector3<f32>) -> DVector<f32> {
+
+        let n = self.positions();
+        let mut state = DVector::<f32>::zeros(2 * n);
+        for i in 0..n {
+            state[i] = point[i];
+        }
+
+        state
+
+    }
+
+}
+
+/// Position part of a [position, velocity] state, planar states lie in the z = 0 plane
+pub(crate) fn position(state: &DVector<f32>) -> Vector3<f32> {
+
+    let mut position = Vector3::<f32>::zeros();
+    for i in 0..(state.len() / 2).min(3) {
+        position[i] = state[i];
+    }
+
+    position
+
+}
 
 pub struct TrackingScenario {
 
     pub num_agents: u32,
     pub num_targets: u32,
+
+    /// Planar or spatial Agent/Target states and dynamics
+    pub dimension: Dimension,
+
     pub agent_formation: Distribution,
-    pub target_formation: Distribution
+    pub target_formation: Distribution,
+    pub assignment_strategy: Box<dyn AssignmentStrategy>,
+
+    /// Compared states and metric used to build the assignment cost matrix
+    pub assignment_cost: AssignmentCost,
+
+    /// Penalizes agents for deviating from their previous assignment
+    pub switching: Option<SwitchingPolicy>,
+
+    /// Decides how often the assignment is recomputed
+    pub assignment_schedule: AssignmentScheduler,
+
+    /// Which agents can exchange information with each other
+    pub communication: Topology,
+
+    /// Which targets each agent can see
+    pub sensing: Topology,
+
+    /// Separation kept between agents by filtering their controls
+    pub collision_avoidance: CollisionAvoidance,
+
+    /// Clearance kept to obstacles by filtering the agent controls, applied whenever there are
+    /// obstacles
+    pub obstacle_avoidance: CollisionAvoidance,
+
+    /// Static and moving obstacles
+    pub obstacles: Vec<ObstacleSpec>,
+
+    /// Cost added to assigning an agent to a target whose straight-line path passes through an
+    /// obstacle, inflated by the obstacle avoidance clearance
+    pub blocked_path_penalty: Option<f64>,
+
+    /// Distances monitored for collisions and near misses
+    pub safety: SafetyConfig,
+
+    /// Placement of the agent formation
+    pub agent_pose: FormationPose,
+
+    /// Standard deviation of the noise added to the initial agent positions
+    pub initial_noise: f32,
+
+    /// Seed of the initial agent position noise
+    pub seed: u64,
+
+    /// Placement of the target formation over time
+    pub target_pose: FormationAnimation,
+
+    /// Motion model of every Target, relative to the target formation
+    pub target_motion: TargetMotion,
+
+    /// Per-agent LQR (Q, R) weights sized to the state dimension, indexed by agent number
+    /// (defaults to identity)
+    pub agent_lqr_weights: Vec<(DMatrix<f32>, DMatrix<f32>)>,
+
+    /// Input, rate and speed limits of every agent
+    pub actuator_limits: ActuatorLimits,
+
+    /// Continuous feedback or zero-order hold of the agent inputs over a simulator step
+    pub control_mode: ControlMode,
+
+    /// Per-agent assignment mass, indexed by agent number (defaults to 1)
+    pub agent_capacities: Vec<f32>,
+
+    /// Per-target assignment mass, indexed by target number (defaults to 1)
+    pub target_demands: Vec<f32>,
+
+    /// Sequence of target formations, overrides target_formation and target_pose when set
+    pub mission: Option<MissionPlan>
 
 }
 
 impl TrackingScenario {
 
-    pub fn new(num_agents: u32, num_targets:u32) -> Self {
+    pub fn new(num_agents: u32, num_targets:u32, assignment_strategy: Box<dyn AssignmentStrategy>) -> Self {
 
-        let agent_formation = Distribution::Sphere;
-        let target_formation = Distribution::Circle3D;
+        let agent_formation = Distribution::Sphere { radius: 10f32 };
+        let target_formation = Distribution::Circle3D { radius: 10f32 };
 
         Self {
             num_agents,
             num_targets,
+            dimension: Dimension::Spatial,
             agent_formation,
-            target_formation
+            target_formation,
+            assignment_strategy,
+            assignment_cost: AssignmentCost::default(),
+            switching: None,
+            assignment_schedule: AssignmentScheduler::new(AssignmentSchedule::EveryStep),
+            communication: Topology::Complete,
+            sensing: Topology::Complete,
+            collision_avoidance: CollisionAvoidance::Disabled,
+            obstacle_avoidance: CollisionAvoidance::obstacle_default(),
+            obstacles: Vec::new(),
+            blocked_path_penalty: None,
+            safety: SafetyConfig::default(),
+            agent_pose: FormationPose::default(),
+            initial_noise: 0f32,
+            seed: 0,
+            target_pose: FormationAnimation::stationary(FormationPose::from_translation(Vector3::new(50.0, 0.0, 0.0))),
+            target_motion: TargetMotion::Stationary,
+            agent_lqr_weights: Vec::new(),
+            actuator_limits: ActuatorLimits::default(),
+            control_mode: ControlMode::Continuous,
+            agent_capacities: Vec::new(),
+            target_demands: Vec::new(),
+            mission: None
         }
 
     }
@@ -46,45 +207,88 @@ impl TrackingScenario {
     // Generate Agent entities and store in a World object
     fn setup_agents(&self, world: &mut World, resources: &mut Resources) {
 
-        let radius = 10f32;
+        // For now just use a double integrator and LQR
+        match self.dimension {
+            Dimension::Planar => {
+                let double_integrator = DoubleIntegrator2DComponent::new();
+                let A = double_integrator.dynamics().A.clone();
+                let B = double_integrator.dynamics().B.clone();
+                self.spawn_agents(world, resources, &A, &B, DoubleIntegrator2DComponent::new);
+            },
+            Dimension::Spatial => {
+                let double_integrator = DoubleIntegrator3DComponent::new();
+                let A = double_integrator.dynamics().A.clone();
+                let B = double_integrator.dynamics().B.clone();
+                self.spawn_agents(world, resources, &A, &B, DoubleIntegrator3DComponent::new);
+            }
+        }
+
+    }
+
+    // Generate Agent entities with a given dynamics model
+    fn spawn_agents<D: Component>(&self,
+        world: &mut World,
+        resources: &mut Resources,
+        A: &DMatrix<f32>,
+        B: &DMatrix<f32>,
+        new_dynamics: fn() -> D
+    )
+    {
+
         let mut storage = resources.get_mut::<SimulationResult>().unwrap();
 
         // Generate initial states
-        let formation = match self.agent_formation {
-            Distribution::Circle2D => circle_3d(radius, self.num_agents), // force 3d scenario
-            Distribution::Circle3D => circle_3d(radius, self.num_agents),
-            Distribution::Sphere => sphere(radius, self.num_agents)
-        };
+        let formation = self.agent_formation.sample(self.num_agents);
+        let formation = self.agent_pose.transform(&formation);
+
+        // Perturb the initial positions
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let formation: Vec<Vector3<f32>> = formation.iter()
+            .map(|point| {
+                if self.initial_noise > 0f32 {
+                    let noise = Vector3::<f32>::from_fn(|_, _| rng.sample(StandardNormal));
+                    point + noise * self.initial_noise
+                } else {
+                    *point
+                }
+            })
+            .collect();
 
-        // For now just use a double integrator and LQR
-        let double_integrator = DoubleIntegrator3DComponent::new();
-        let A = double_integrator.dynamics().A.clone();
-        let B = double_integrator.dynamics().B.clone();
-        let Q = DMatrix::<f32>::identity(6, 6);
-        let R = DMatrix::<f32>::identity(3, 3);
+        let Q = DMatrix::<f32>::identity(A.nrows(), A.ncols());
+        let R = DMatrix::<f32>::identity(B.ncols(), B.ncols());
 
         // Define agent components
-        let agent_components: Vec<(FullState, DoubleIntegrator3DComponent, LQRComponent, SimID, Agent)> = (0..self.num_agents).into_iter()
+        let agent_components: Vec<(FullState, D, LQRComponent, SimID, Agent, Capacity, ActuatorState)> = (0..self.num_agents)
             .zip(formation.iter())
-            .map(| (i, pose) | -> (FullState, DoubleIntegrator3DComponent, LQRComponent, SimID, Agent) {
+            .map(| (i, pose) | -> (FullState, D, LQRComponent, SimID, Agent, Capacity, ActuatorState) {
 
                 let name = "Agent".to_string() + &i.to_string();
                 let id = Uuid::new_v4();
                 let sim_id = SimID { uuid: id, name };
 
                 // Initial conditions
-                let state = DVector::<f32>::from_vec(vec![pose.0, pose.1, pose.2, 0.0, 0.0, 0.0]);
+                let state = self.dimension.state_at_rest(pose);
                 let fullstate = FullState { data: state };
 
                 // Agent dynamics model
-                let dynamics = DoubleIntegrator3DComponent::new();
+                let dynamics = new_dynamics();
 
                 // Agent controller
-                let controller = LQRComponent::new(A.clone(), B.clone(), Q.clone(), R.clone());
+                let (Q_i, R_i) = match self.agent_lqr_weights.get(i as usize) {
+                    Some((Q_i, R_i)) => (Q_i.clone(), R_i.clone()),
+                    None => (Q.clone(), R.clone())
+                };
+                let controller = LQRComponent::new(A.clone(), B.clone(), Q_i, R_i);
 
                 let agent_flag = Agent { 0: true };
 
-                (fullstate, dynamics, controller, sim_id, agent_flag)
+                // Agent assignment mass
+                let capacity = Capacity(*self.agent_capacities.get(i as usize).unwrap_or(&1f32));
+
+                // Agent actuators
+                let actuator = ActuatorState::new(self.actuator_limits.clone());
+
+                (fullstate, dynamics, controller, sim_id, agent_flag, capacity, actuator)
             })
             .collect();
 
@@ -101,45 +305,61 @@ impl TrackingScenario {
     // Generate Target entities and store in a World object
     fn setup_targets(&self, world: &mut World, resources: &mut Resources) {
 
+        // For now just use a double integrator and LQR
+        match self.dimension {
+            Dimension::Planar => {
+                let double_integrator = DoubleIntegrator2DComponent::new();
+                let A = double_integrator.dynamics().A.clone();
+                let B = double_integrator.dynamics().B.clone();
+                self.spawn_targets(world, resources, &A, &B, DoubleIntegrator2DComponent::new);
+            },
+            Dimension::Spatial => {
+                let double_integrator = DoubleIntegrator3DComponent::new();
+                let A = double_integrator.dynamics().A.clone();
+                let B = double_integrator.dynamics().B.clone();
+                self.spawn_targets(world, resources, &A, &B, DoubleIntegrator3DComponent::new);
+            }
+        }
+
+    }
+
+    // Generate Target entities with a given dynamics model
+    fn spawn_targets<D: Component>(&self,
+        world: &mut World,
+        resources: &mut Resources,
+        A: &DMatrix<f32>,
+        B: &DMatrix<f32>,
+        new_dynamics: fn() -> D
+    )
+    {
+
         let mut storage = resources.get_mut::<SimulationResult>().unwrap();
         let mut targetable_set = resources.get_mut::<TargetableSet>().unwrap();
 
         // Generate initial states
-        let radius = 10f32;
-        let mut formation = match self.target_formation{
-            Distribution::Circle2D => circle_3d(radius, self.num_targets), // force 3d scenario
-            Distribution::Circle3D => circle_3d(radius, self.num_targets),
-            Distribution::Sphere => sphere(radius, self.num_targets)
-        };
-
-        // shift the distribution over
-        for (x, _y, _z) in formation.iter_mut() {
-            *x += 50.0;
-        }
+        let (target_formation, target_pose) = self.initial_target_formation();
+        let formation = target_formation.sample(self.num_targets);
 
-        // For now just use a double integrator and LQR
-        let double_integrator = DoubleIntegrator3DComponent::new();
-        let A = double_integrator.dynamics().A.clone();
-        let B = double_integrator.dynamics().B.clone();
-        let Q = DMatrix::<f32>::identity(6, 6);
-        let R = DMatrix::<f32>::identity(3, 3);
+        let Q = DMatrix::<f32>::identity(A.nrows(), A.ncols());
+        let R = DMatrix::<f32>::identity(B.ncols(), B.ncols());
 
         // Define target components
-        let target_components: Vec<(FullState, DoubleIntegrator3DComponent, LQRComponent, SimID, Target)>
-            = (0..self.num_targets).into_iter()
+        let target_components: Vec<(FullState, D, LQRComponent, SimID, Target, Demand, TargetTrajectory)>
+            = (0..self.num_targets)
             .zip(formation.iter())
-            .map(| (i, pose) | -> (FullState, DoubleIntegrator3DComponent, LQRComponent, SimID, Target) {
+            .map(| (i, pose) | -> (FullState, D, LQRComponent, SimID, Target, Demand, TargetTrajectory) {
 
                 let name = "Target".to_string() + &i.to_string();
                 let id = Uuid::new_v4();
                 let sim_id = SimID { uuid: id, name };
 
-                // Initial conditions
-                let state = DVector::<f32>::from_vec(vec![pose.0, pose.1, pose.2, 0.0, 0.0, 0.0]);
+                // Formation slot and initial conditions
+                let slot = self.dimension.state_at_rest(pose);
+                let state = target_pose.transform_state(0f32, &self.target_motion.reference(0f32, &slot));
                 let fullstate = FullState { data: state };
 
                 // Target dynamics
-                let dynamics = DoubleIntegrator3DComponent::new();
+                let dynamics = new_dynamics();
 
                 // Target controllers
                 let controller = LQRComponent::new(A.clone(), B.clone(), Q.clone(), R.clone());
@@ -147,7 +367,13 @@ impl TrackingScenario {
                 // Identifier flag
                 let target_flag = Target { 0: true };
 
-                (fullstate, dynamics, controller, sim_id, target_flag)
+                // Target assignment mass
+                let demand = Demand(*self.target_demands.get(i as usize).unwrap_or(&1f32));
+
+                // Target motion starting from its formation slot
+                let trajectory = TargetTrajectory { motion: self.target_motion.clone(), origin: slot, start_time: 0f32, slot: i };
+
+                (fullstate, dynamics, controller, sim_id, target_flag, demand, trajectory)
 
             })
             .collect();
@@ -164,6 +390,87 @@ impl TrackingScenario {
 
     }
 
+    /// Target formation and pose at the initial time, given by the first mission phase if any
+    fn initial_target_formation(&self) -> (Distribution, FormationAnimation) {
+
+        match self.mission.as_ref().and_then(|mission| mission.phases.first()) {
+            Some(phase) => (phase.formation.clone(), phase.pose.clone()),
+            None => (self.target_formation.clone(), self.target_pose.clone())
+        }
+
+    }
+
+    // Generate Obstacle entities and store in a World object
+    fn setup_obstacles(&self, world: &mut World, resources: &mut Resources) {
+
+        let mut storage = resources.get_mut::<SimulationResult>().unwrap();
+
+        // Define obstacle components
+        let obstacle_components: Vec<(FullState, SimID, Obstacle, ObstacleShape, ObstacleTrajectory)> = self.obstacles.iter()
+            .enumerate()
+            .map(|(i, obstacle)| -> (FullState, SimID, Obstacle, ObstacleShape, ObstacleTrajectory) {
+
+                let name = "Obstacle".to_string() + &i.to_string();
+                let id = Uuid::new_v4();
+                let sim_id = SimID { uuid: id, name };
+
+                // Initial conditions
+                let origin = self.dimension.state_at_rest(&obstacle.position);
+                let fullstate = FullState { data: obstacle.motion.reference(0f32, &origin) };
+
+                let obstacle_flag = Obstacle { 0: true };
+
+                // Obstacle motion starting from its initial position
+                let trajectory = ObstacleTrajectory { motion: obstacle.motion.clone(), origin };
+
+                (fullstate, sim_id, obstacle_flag, obstacle.shape.clone(), trajectory)
+
+            })
+            .collect();
+
+        // Add obstacles to storage resource
+        for obstacle in obstacle_components.iter() {
+            storage.data.entry(obstacle.1.clone()).or_insert(vec![obstacle.0.clone()]);
+        }
+
+        // Generate Obstacle Entities defined by component tuples and add to the World
+        let _obstacles: &[Entity] = world.extend(obstacle_components);
+
+    }
+
+    /// Snapshots the Obstacle shapes and states for the Agents to avoid
+    fn update_obstacle_states(&self, world: &mut World, resources: &mut Resources) {
+
+        let mut obstacle_states = resources.get_mut::<ObstacleStates>().unwrap();
+
+        let mut query = <(&SimID, &FullState, &ObstacleShape, &Obstacle)>::query();
+        obstacle_states.map = query.iter(world)
+            .map(|(id, state, shape, _obstacle)| (id.uuid, (shape.clone(), state.data.clone())))
+            .collect();
+
+    }
+
+    /// Records agent-agent distances and agent-obstacle clearances
+    fn update_safety_monitor(&self, world: &mut World, resources: &mut Resources) {
+
+        let mut monitor = resources.get_mut::<SafetyMonitor>().unwrap();
+        let time = resources.get::<SimulationTime>().unwrap().0;
+
+        let mut agent_query = <(&SimID, &FullState, &Agent)>::query();
+        let mut obstacle_query = <(&SimID, &FullState, &ObstacleShape, &Obstacle)>::query();
+
+        let agents: Vec<(&str, Vector3<f32>)> = agent_query.iter(world)
+            .map(|(id, state, _agent)| (id.name.as_str(), position(&state.data)))
+            .collect();
+
+        let obstacles: Vec<(&str, &ObstacleShape, Vector3<f32>)> = obstacle_query.iter(world)
+            .map(|(id, state, shape, _obstacle)| (id.name.as_str(), shape, position(&state.data)))
+            .collect();
+
+        monitor.record(time, &agents, &obstacles);
+
+    }
+
     /// Keeps track of Entities that have a Target component
     fn update_targetable_set(&self, world: &mut World, resources: &mut Resources) {
 
@@ -186,56 +493,321 @@ impl TrackingScenario {
 
     }
 
-    /// Generates an assignment between Agent and Target Entitites based off of position
-    fn assign(&self, world: &mut World, resources: &mut Resources) {
+    /// Resolves the assignment cost metric of every Agent. The LQR weights are static so each
+    /// Riccati equation is solved once
+    fn setup_agent_cost_metrics(&self, world: &mut World, resources: &mut Resources) {
+
+        let mut agent_cost_metrics = resources.get_mut::<AgentCostMetrics>().unwrap();
+
+        if let CostMetric::LqrCostToGo = self.assignment_cost.metric {
+            let mut controller_query = <(&SimID, &LQRComponent, &Agent)>::query();
+            agent_cost_metrics.map = controller_query.iter(world)
+                .filter_map(|(id, controller, _agent)| {
+                    CostMetric::lqr_cost_to_go(controller).map(|metric| (id.uuid, metric))
+                })
+                .collect();
+        }
+
+    }
+
+    /// Snapshots the Agent states for collision avoidance
+    fn update_agent_states(&self, world: &mut World, resources: &mut Resources) {
+
+        let mut agent_states = resources.get_mut::<AgentStates>().unwrap();
+
+        let mut query = <(&SimID, &FullState, &Agent)>::query();
+        agent_states.map = query.iter(world)
+            .map(|(id, state, _agent)| (id.uuid, state.data.clone()))
+            .collect();
+
+    }
+
+    /// Rebuilds the communication/sensing graph from the current Agent and Target positions
+    fn update_communication_graph(&self, world: &mut World, resources: &mut Resources) {
+
+        let mut graph = resources.get_mut::<CommunicationGraph>().unwrap();
+
+        let mut agent_query = <(&SimID, &FullState, &Agent)>::query();
+        let mut target_query = <(&SimID, &FullState, &Target)>::query();
+
+        let agents: Vec<Node> = agent_query.iter(world)
+            .map(|(id, state, _agent)| {
+                let p = position(&state.data);
+                Node { uuid: id.uuid, name: &id.name, position: [p.x, p.y, p.z] }
+            })
+            .collect();
+
+        let targets: Vec<Node> = target_query.iter(world)
+            .map(|(id, state, _target)| {
+                let p = position(&state.data);
+                Node { uuid: id.uuid, name: &id.name, position: [p.x, p.y, p.z] }
+            })
+            .collect();
+
+        graph.agent_neighbors = build_edges(&agents, &agents, &self.communication);
+        graph.visible_targets = build_edges(&agents, &targets, &self.sensing);
+
+    }
+
+    /// Generates an assignment between Agent and Target Entitites based off of their states
+    /// Returns whether an assignment was recorded, the previous assignment is kept on failure
+    fn assign(&self, world: &mut World, resources: &mut Resources) -> bool {
 
         let assignment: Vec<Vec<u32>>;
 
         // Resources
         let mut assignment_history = resources.get_mut::<AssignmentHistory>().unwrap();
-        let mut current_assignment = resources.get_mut::<Assignment>().unwrap();
+        let time = resources.get::<SimulationTime>().unwrap().0;
 
         // Query entities
         let mut target_query = <(&SimID, &FullState, &Target)>::query();
         let mut agent_query = <(&SimID, &FullState, &Agent)>::query();
 
-        // Agent entity positions and ids
+        let mut agent_weight_query = <(&SimID, &Agent, Option<&Capacity>)>::query();
+        let mut target_weight_query = <(&SimID, &Target, Option<&Demand>)>::query();
+
+        // Agent entity states and ids
         let mut agent_states: Vec<Vec<f32>> = Vec::new();
         let mut agent_ids: Vec<&Uuid> = Vec::new();
+        let mut agent_positions: Vec<Vector3<f32>> = Vec::new();
         for (id, state, _agent) in agent_query.iter(world) {
-            let pose = vec![state.data[0], state.data[1], state.data[2]];
-            agent_states.push(pose);
+            agent_states.push(self.assignment_cost.project(&state.data));
             agent_ids.push(&id.uuid);
+            agent_positions.push(position(&state.data));
         }
 
-        // Target entity positions and ids
+        // Target entity states and ids
         let mut target_states: Vec<Vec<f32>> = Vec::new();
         let mut target_ids: Vec<&Uuid> = Vec::new();
+        let mut target_positions: Vec<Vector3<f32>> = Vec::new();
         for (id, state, _target) in target_query.iter(world) {
-            let pose = vec![state.data[0], state.data[1], state.data[2]];
-            target_states.push(pose);
+            target_states.push(self.assignment_cost.project(&state.data));
             target_ids.push(&id.uuid);
+            target_positions.push(position(&state.data));
         }
 
-        // Perform assignment of agents to targets
-        assignment = match ot_assignment(&agent_states, &target_states) {
+        // Agent/Target assignment masses, entities without a Capacity/Demand carry unit mass
+        let capacities: HashMap<Uuid, f64> = agent_weight_query.iter(world)
+            .map(|(id, _agent, capacity)| (id.uuid, capacity.map_or(1f64, |c| c.0 as f64)))
+            .collect();
+        let demands: HashMap<Uuid, f64> = target_weight_query.iter(world)
+            .map(|(id, _target, demand)| (id.uuid, demand.map_or(1f64, |d| d.0 as f64)))
+            .collect();
+
+        let mut problem = match self.assignment_cost.metric {
+
+            // Each agent is evaluated under the Riccati solution of its own controller
+            CostMetric::LqrCostToGo => {
+                let agent_cost_metrics = resources.get::<AgentCostMetrics>().unwrap();
+                let agent_metrics: Vec<CostMetric> = agent_ids.iter()
+                    .map(|id| agent_cost_metrics.map.get(*id).cloned().unwrap_or(CostMetric::SqEuclidean))
+                    .collect();
+
+                AssignmentProblem::with_agent_metrics(agent_states, target_states, &agent_metrics)
+            },
+
+            _ => AssignmentProblem::new(agent_states, target_states, &self.assignment_cost.metric)
+
+        };
+        problem.agent_weights = agent_ids.iter().map(|id| capacities[*id]).collect();
+        problem.target_weights = target_ids.iter().map(|id| demands[*id]).collect();
+
+        // Restrict agents to what they can communicate with and sense
+        let graph = resources.get::<CommunicationGraph>().unwrap();
+        problem.agent_adjacency = Some(to_adjacency_matrix(&graph.agent_neighbors, &agent_ids, &agent_ids));
+        problem.set_target_visibility(to_adjacency_matrix(&graph.visible_targets, &agent_ids, &target_ids));
+
+        // Penalize deviating from the previous assignment
+        if let Some(switching) = &self.switching {
+
+            let current: Vec<Option<usize>> = agent_ids.iter()
+                .map(|agent_id| {
+                    assignment_history.current_target(agent_id)
+                        .and_then(|target_id| target_ids.iter().position(|id| *id == target_id))
+                })
+                .collect();
+            let dwell: Vec<f32> = agent_ids.iter()
+                .map(|agent_id| assignment_history.dwell_time(agent_id, time))
+                .collect();
+
+            switching.apply(&mut problem.cost, &current, &dwell);
+
+        }
+
+        // Penalize straight-line paths through obstacles
+        if let Some(penalty) = self.blocked_path_penalty {
+
+            let obstacle_states = resources.get::<ObstacleStates>().unwrap();
+            let margin = self.obstacle_avoidance.min_separation();
 
-            Ok(ot_matrix) => ot_matrix,
-            Err(error) => panic!("EMD assignment error {:?}", error)
+            for (i, agent_position) in agent_positions.iter().enumerate() {
+                for (j, target_position) in target_positions.iter().enumerate() {
+                    let blocked = obstacle_states.map.values().any(|(shape, obstacle)| {
+                        shape.blocks(&position(obstacle), agent_position, target_position, margin)
+                    });
+                    if blocked {
+                        problem.cost[(i, j)] += penalty;
+                    }
+                }
+            }
+
+        }
+
+        // Perform assignment of agents to targets
+        // On failure keep the previous assignment
+        let stats = match self.assignment_strategy.assign_with_stats(&problem) {
+
+            Ok(outcome) => {
+                assignment = outcome.coupling;
+                outcome.stats
+            },
+            Err(error) => {
+                println!("assignment error, {}", error);
+                resources.get_mut::<SolverConvergence>().unwrap().failed += 1;
+                return false;
+            }
 
         };
 
+        // Iterative solvers that stopped at their round limit may disagree on the winners
+        if let Some(stats) = stats {
+            let mut solver_convergence = resources.get_mut::<SolverConvergence>().unwrap();
+            solver_convergence.record(&stats);
+            if !stats.converged {
+                println!("assignment error, solver did not converge after {} rounds", stats.rounds);
+                solver_convergence.failed += 1;
+                return false;
+            }
+        }
+
         // Update AssignmentHistory resource
         for (i, agent) in assignment.iter().enumerate() {
-            for (j, possible_target) in agent.iter().enumerate() {
-                if *possible_target == 1 {
-                    let agent_id = agent_ids[i];
-                    let target_id = target_ids[j];
-                    assignment_history.map.entry(*agent_id).or_insert(vec![*target_id]).push(*target_id);
+            let targets: Vec<Uuid> = agent.iter().enumerate()
+                .filter(|(_j, possible_target)| **possible_target == 1)
+                .map(|(j, _possible_target)| *target_ids[j])
+                .collect();
+            assignment_history.record(*agent_ids[i], &targets, time);
+        }
+
+        true
+
+    }
+
+    /// Whether the assignment is due this iteration according to the assignment schedule
+    fn assignment_due(&self, world: &World, resources: &Resources) -> bool {
+
+        let time = resources.get::<SimulationTime>().unwrap().0;
+
+        // Largest agent position error with respect to its assigned target
+        let max_tracking_error = if self.assignment_schedule.needs_tracking_error() {
+            self.agent_tracking_errors(world, resources).iter()
+                .filter_map(|error| *error)
+                .fold(0f32, f32::max)
+        } else {
+            0f32
+        };
+
+        let target_positions = if self.assignment_schedule.needs_target_motion() {
+            self.target_positions(world)
+        } else {
+            HashMap::new()
+        };
+
+        self.assignment_schedule.is_due(time, max_tracking_error, &target_positions)
+
+    }
+
+    /// Position error of each Agent with respect to its assigned target, None if unassigned
+    fn agent_tracking_errors(&self, world: &World, resources: &Resources) -> Vec<Option<f32>> {
+
+        let current_assignment = resources.get::<Assignment>().unwrap();
+        let mut agent_query = <(&SimID, &FullState, &Agent)>::query();
+
+        agent_query.iter(world)
+            .map(|(id, state, _agent)| {
+                match current_assignment.map.get(&id.uuid) {
+                    Some(Some(target_state)) => Some((position(&state.data) - position(target_state)).norm()),
+                    _ => None
+                }
+            })
+            .collect()
+
+    }
+
+    /// Records mission metrics and moves the targets to the next formation once the current
+    /// phase is complete
+    fn update_mission(&mut self, world: &mut World, resources: &mut Resources) {
+
+        if self.mission.is_none() {
+            return;
+        }
+
+        let time = resources.get::<SimulationTime>().unwrap().0;
+        let errors = self.agent_tracking_errors(world, resources);
+
+        let mission = self.mission.as_mut().unwrap();
+        let mut mission_log = resources.get_mut::<MissionLog>().unwrap();
+        mission_log.update(&errors);
+
+        // Check the transition condition of the active phase
+        let phase_start = mission_log.phases.last().map_or(0f32, |record| record.start_time);
+        let complete = match mission.current() {
+            Some(phase) => phase.transition.is_met(time - phase_start, &errors),
+            None => false
+        };
+        if !complete {
+            return;
+        }
+
+        let phase = match mission.advance() {
+            Some(phase) => phase.clone(),
+            None => return // the last phase is held
+        };
+
+        mission_log.end(time);
+        mission_log.begin(mission.current_index(), time);
+
+        // Move each target's formation slot to the new formation
+        let formation = phase.formation.sample(self.num_targets);
+        let mut trajectory_query = <(&mut TargetTrajectory, &Target)>::query();
+        for (trajectory, _target) in trajectory_query.iter_mut(world) {
+            if let Some(pose) = formation.get(trajectory.slot as usize) {
+                for i in 0..self.dimension.positions() {
+                    trajectory.origin[i] = pose[i];
                 }
+                trajectory.start_time = time;
             }
         }
 
+        *resources.get_mut::<FormationAnimation>().unwrap() = phase.pose.starting_at(time);
+
+        // Targets have changed, reassign at the next opportunity
+        self.assignment_schedule.reset();
+
+    }
+
+    /// Target positions keyed by uuid
+    fn target_positions(&self, world: &World) -> HashMap<Uuid, Vec<f32>> {
+
+        let mut target_query = <(&SimID, &FullState, &Target)>::query();
+        target_query.iter(world)
+            .map(|(id, state, _target)| {
+                let p = position(&state.data);
+                (id.uuid, vec![p.x, p.y, p.z])
+            })
+            .collect()
+
+    }
+
+    /// Refreshes the states of the assigned targets in the current Assignment resource
+    fn update_assignment_targets(&self, world: &mut World, resources: &mut Resources) {
+
+        let assignment_history = resources.get::<AssignmentHistory>().unwrap();
+        let mut current_assignment = resources.get_mut::<Assignment>().unwrap();
+
+        let mut target_query = <(&SimID, &FullState, &Target)>::query();
+        let mut agent_query = <(&SimID, &FullState, &Agent)>::query();
+
         // Update current Assignment resource
         for (_i, (agent_id, _agent_state, _agent)) in agent_query.iter(world).enumerate() {
 
@@ -256,9 +828,6 @@ impl TrackingScenario {
 
         }
 
-        // println!("{:?}", assignment);
-
-
     }
 
 }
@@ -267,14 +836,37 @@ impl Default for TrackingScenario {
 
     fn default() -> Self {
 
-        let agent_formation = Distribution::Sphere;
-        let target_formation = Distribution::Circle3D;
+        let agent_formation = Distribution::Sphere { radius: 10f32 };
+        let target_formation = Distribution::Circle3D { radius: 10f32 };
 
         Self {
             num_agents: 50,
             num_targets: 50,
+            dimension: Dimension::Spatial,
             agent_formation,
-            target_formation
+            target_formation,
+            assignment_strategy: Box::new(OTAssignment::default()),
+            assignment_cost: AssignmentCost::default(),
+            switching: None,
+            assignment_schedule: AssignmentScheduler::new(AssignmentSchedule::EveryStep),
+            communication: Topology::Complete,
+            sensing: Topology::Complete,
+            collision_avoidance: CollisionAvoidance::Disabled,
+            obstacle_avoidance: CollisionAvoidance::obstacle_default(),
+            obstacles: Vec::new(),
+            blocked_path_penalty: None,
+            safety: SafetyConfig::default(),
+            agent_pose: FormationPose::default(),
+            initial_noise: 0f32,
+            seed: 0,
+            target_pose: FormationAnimation::stationary(FormationPose::from_translation(Vector3::new(50.0, 0.0, 0.0))),
+            target_motion: TargetMotion::Stationary,
+            agent_lqr_weights: Vec::new(),
+            actuator_limits: ActuatorLimits::default(),
+            control_mode: ControlMode::Continuous,
+            agent_capacities: Vec::new(),
+            target_demands: Vec::new(),
+            mission: None
         }
 
     }
@@ -292,28 +884,78 @@ impl Scenario for TrackingScenario {
         // scenario resources
         let num_agents = NumAgents(self.num_agents);
         let num_targets = NumTargets(self.num_targets);
+        let num_obstacles = NumObstacles(self.obstacles.len() as u32);
         let targetable_set = TargetableSet(HashMap::new());
         let assignment = Assignment{ map: HashMap::new() };
-        let assignment_history = AssignmentHistory{ map: HashMap::new() };
+        let assignment_history = AssignmentHistory::default();
+        let communication_graph = CommunicationGraph::default();
+        let agent_states = AgentStates::default();
+        let agent_cost_metrics = AgentCostMetrics::default();
+        let solver_convergence = SolverConvergence::default();
+        let obstacle_states = ObstacleStates::default();
+        let safety_monitor = SafetyMonitor::new(self.safety.clone());
         let storage = SimulationResult{ data: HashMap::new() };
         resources.insert(num_agents);
         resources.insert(num_targets);
+        resources.insert(num_obstacles);
         resources.insert(targetable_set);
         resources.insert(assignment);
         resources.insert(assignment_history);
+        resources.insert(communication_graph);
+        resources.insert(agent_states);
+        resources.insert(agent_cost_metrics);
+        resources.insert(solver_convergence);
+        resources.insert(obstacle_states);
+        resources.insert(safety_monitor);
+        resources.insert(self.collision_avoidance.clone());
+        resources.insert(ObstacleAvoidance(self.obstacle_avoidance.clone()));
+        resources.insert(self.control_mode);
         resources.insert(storage);
+        let (_target_formation, target_pose) = self.initial_target_formation();
+        resources.insert(target_pose);
+
+        // Mission metrics, starting with the first phase
+        let mission_log = match &self.mission {
+            Some(mission) => {
+                let mut mission_log = MissionLog::new(mission.tolerance);
+                mission_log.begin(mission.current_index(), 0f32);
+                mission_log
+            },
+            None => MissionLog::default()
+        };
+        resources.insert(mission_log);
 
         self.setup_agents(world, resources);
         self.setup_targets(world, resources);
+        self.setup_obstacles(world, resources);
+        self.setup_agent_cost_metrics(world, resources);
+        self.update_agent_states(world, resources);
+        self.update_obstacle_states(world, resources);
 
     }
 
     /// Defines a schedule of Systems to execute per simulator iteration
     fn build(&self) -> Schedule {
 
-        let schedule = Schedule::builder()
-            .add_system(print_time_system())
-            .add_system(integrate_lqr_error_dynamics_system::<DoubleIntegrator3DComponent>())
+        let mut builder = Schedule::builder();
+        builder.add_system(print_time_system());
+
+        match self.dimension {
+            Dimension::Planar => {
+                builder.add_system(integrate_lqr_error_dynamics_system::<DoubleIntegrator2DComponent>());
+                builder.add_system(propagate_targets_system::<DoubleIntegrator2DComponent>());
+            },
+            Dimension::Spatial => {
+                builder.add_system(integrate_lqr_error_dynamics_system::<DoubleIntegrator3DComponent>());
+                builder.add_system(propagate_targets_system::<DoubleIntegrator3DComponent>());
+            }
+        }
+
+        if !self.obstacles.is_empty() {
+            builder.add_system(propagate_obstacles_system());
+        }
+
+        let schedule = builder
             .add_system(update_result_system())
             .add_system(increment_time_system())
             .build();
@@ -325,11 +967,38 @@ impl Scenario for TrackingScenario {
     /// Update scenario
     fn update(&mut self, world: &mut World, resources: &mut Resources) {
 
+        // Advances the mission once the current phase is complete
+        self.update_mission(world, resources);
+
         // Updates entities flagged as Targetable
         self.update_targetable_set(world, resources);
 
-        // Perform assignment of Agents to Targets
-        self.assign(world, resources);
+        // Updates the agent and obstacle states seen by the agents
+        if self.collision_avoidance.is_enabled() {
+            self.update_agent_states(world, resources);
+        }
+        if !self.obstacles.is_empty() {
+            self.update_obstacle_states(world, resources);
+        }
+
+        // Collision and near-miss metrics
+        self.update_safety_monitor(world, resources);
+
+        // Updates who can communicate with and sense whom
+        self.update_communication_graph(world, resources);
+
+        // Perform assignment of Agents to Targets when scheduled, a failed assignment is retried at
+        // the next iteration
+        if self.assignment_due(world, resources) && self.assign(world, resources) {
+            let time = resources.get::<SimulationTime>().unwrap().0;
+            let target_positions = self.target_positions(world);
+            self.assignment_schedule.assigned(time, target_positions);
+        } else {
+            self.assignment_schedule.skip();
+        }
+
+        // Assigned targets may have moved since the last assignment
+        self.update_assignment_targets(world, resources);
 
     }
 